@@ -0,0 +1,50 @@
+//! Benchmarks of the cell orders of the solver on the hard puzzles of
+//! tests/data/hard-puzzles.txt, printing the number of guesses each order needs.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use sudoku_rs::{
+    input::SudokuGrid,
+    solver::{CellOrder, Solver, SolverConfig},
+};
+
+fn cell_orders(c: &mut Criterion) {
+    let puzzles: Vec<SudokuGrid> = include_str!("../tests/data/hard-puzzles.txt")
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect();
+    let mut group = c.benchmark_group("hard puzzles");
+    for (name, cell_order) in [
+        ("mrv", CellOrder::Mrv),
+        ("mrv+degree", CellOrder::MostConstrainingVariable),
+    ] {
+        let config = SolverConfig {
+            cell_order,
+            ..SolverConfig::default()
+        };
+        let guesses: u64 = puzzles
+            .iter()
+            .map(|puzzle| {
+                let solver = Solver::from_grid(puzzle.clone()).with_config(config);
+                solver.solve_with_stats().1.guesses
+            })
+            .sum();
+        println!("{}: {} guesses", name, guesses);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for puzzle in &puzzles {
+                    black_box(
+                        Solver::from_grid(puzzle.clone())
+                            .with_config(config)
+                            .solve(),
+                    );
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cell_orders);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+//! Benchmarks of the grid operations on the hot path of the solver.
+//!
+//! To check a change for regressions, save a baseline before making it with
+//! `cargo bench --bench grid -- --save-baseline before`, and compare against it
+//! afterwards with `cargo bench --bench grid -- --baseline before`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use sudoku_rs::{input::SudokuGrid, solver::Solver};
+
+/// The puzzle from the Wikipedia article on Sudoku
+const PUZZLE: &str =
+    "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+/// The solution of `PUZZLE`
+const SOLUTION: &str =
+    "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+fn set_and_unset(c: &mut Criterion) {
+    let solution: SudokuGrid = SOLUTION.parse().unwrap();
+    let mut grid = SudokuGrid::default();
+    c.bench_function("set and unset every cell", |b| {
+        b.iter(|| {
+            for (row, col, value) in solution.iter_cells() {
+                assert!(grid.set(row, col, black_box(value)));
+            }
+            for (row, col, _) in solution.iter_cells() {
+                grid.unset(row, col);
+            }
+        })
+    });
+}
+
+fn candidates(c: &mut Criterion) {
+    let puzzle: SudokuGrid = PUZZLE.parse().unwrap();
+    c.bench_function("candidates of every cell", |b| {
+        b.iter(|| {
+            (0..81)
+                .map(|k| {
+                    black_box(&puzzle)
+                        .candidates_mask(k / 9, k % 9)
+                        .count_ones()
+                })
+                .sum::<u32>()
+        })
+    });
+}
+
+fn solve(c: &mut Criterion) {
+    let puzzle: SudokuGrid = PUZZLE.parse().unwrap();
+    c.bench_function("solve the Wikipedia puzzle", |b| {
+        b.iter(|| Solver::from_grid(black_box(puzzle.clone())).solve())
+    });
+}
+
+criterion_group!(benches, set_and_unset, candidates, solve);
+criterion_main!(benches);
@@ -0,0 +1,389 @@
+//! Solve grids as an exact cover problem with Knuth's Algorithm X and dancing links.
+//!
+//! Each way to place a digit in a cell is a row of the matrix, and each constraint
+//! (a cell holds one digit, and a row, column, square, or unit added by the variant
+//! holds each digit once)
+//! is a column. A solution is a set of rows that covers every column exactly once.
+//!
+//! The rule of `Variant::NonConsecutive` is not about covering, so it is checked
+//! separately: a row is skipped if its digit is consecutive to an adjacent placement.
+//! The rule then only prunes rows as they are tried, so nonconsecutive grids with few
+//! clues are much slower to solve this way than with `Solver`.
+
+use crate::{
+    input::{orthogonal_neighbors, SudokuGrid, Variant, SQUARE_OF},
+    solver::SolveStats,
+};
+
+/// Solve a grid with dancing links, returning the solution if there is one.
+pub fn solve(grid: &SudokuGrid) -> Option<SudokuGrid> {
+    solve_with_stats(grid).0
+}
+
+/// Solve a grid with dancing links and count the rows of the matrix the search tried.
+///
+/// Every row selected during the search counts as a guess, including the rows that are
+/// the only way left to cover a column, and every row taken back counts as a backtrack.
+pub fn solve_with_stats(grid: &SudokuGrid) -> (Option<SudokuGrid>, SolveStats) {
+    let mut links = Links::new(grid);
+    let mut stats = SolveStats::default();
+    let mut selected = Vec::new();
+    if !links.search(&mut selected, &mut stats) {
+        return (None, stats);
+    }
+
+    let mut solution = grid.clone();
+    for node in selected {
+        let (row, col, value) = links.candidate[node];
+        if !solution.set(row, col, value) {
+            return (None, stats);
+        }
+    }
+    (Some(solution), stats)
+}
+
+/// The sparse exact cover matrix as nodes doubly linked in four directions.
+///
+/// Node 0 is the root, the next nodes are the column headers,
+/// and the rest are the 1s of the matrix.
+struct Links {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    /// Column header of each node
+    column: Vec<usize>,
+    /// Number of nodes left in each column, indexed by the column header
+    size: Vec<usize>,
+    /// The placement `(row, col, value)` of the matrix row each node belongs to
+    candidate: Vec<(usize, usize, u8)>,
+    /// Whether adjacent cells must not hold consecutive digits
+    nonconsecutive: bool,
+    /// The values of the clues and of the selected rows
+    values: [[u8; 9]; 9],
+}
+
+impl Links {
+    /// Build the matrix of the placements that are still possible in a grid.
+    /// The clues of the grid are covered before the search starts.
+    fn new(grid: &SudokuGrid) -> Self {
+        // Number the units added by the variant after the 324 columns of classic Sudoku
+        let mut extra_units = Vec::new();
+        for (row, col, _) in grid.iter_cells() {
+            for unit in grid.extra_units_of(row, col) {
+                if !extra_units.contains(&unit) {
+                    extra_units.push(unit);
+                }
+            }
+        }
+        let columns = 324 + extra_units.len() * 9;
+        let mut links = Self {
+            left: (0..=columns)
+                .map(|i| (i + columns) % (columns + 1))
+                .collect(),
+            right: (0..=columns).map(|i| (i + 1) % (columns + 1)).collect(),
+            up: (0..=columns).collect(),
+            down: (0..=columns).collect(),
+            column: (0..=columns).collect(),
+            size: vec![0; columns + 1],
+            candidate: vec![(0, 0, 0); columns + 1],
+            nonconsecutive: grid.variant().contains(Variant::NonConsecutive),
+            values: std::array::from_fn(|row| *grid.row_slice(row)),
+        };
+
+        let mut clues = Vec::new();
+        for (row, col, value) in grid.iter_cells() {
+            let values: Vec<u8> = if value != 0 {
+                vec![value]
+            } else {
+                grid.candidates(row, col).collect()
+            };
+            for value in values {
+                let digit = value as usize - 1;
+                let mut constraints = vec![
+                    row * 9 + col,
+                    81 + row * 9 + digit,
+                    162 + col * 9 + digit,
+                    243 + SQUARE_OF[row][col] * 9 + digit,
+                ];
+                for unit in grid.extra_units_of(row, col) {
+                    // Safe to unwrap because every unit of every cell was numbered above
+                    let k = extra_units.iter().position(|&u| u == unit).unwrap();
+                    constraints.push(324 + k * 9 + digit);
+                }
+                let first = links.add_row(&constraints, (row, col, value));
+                if grid.at(row, col) != 0 {
+                    clues.push(first);
+                }
+            }
+        }
+        for node in clues {
+            links.select(node);
+        }
+        links
+    }
+
+    /// Append a row with a 1 in each of the given constraint columns.
+    /// Returns the first node of the row.
+    fn add_row(&mut self, constraints: &[usize], candidate: (usize, usize, u8)) -> usize {
+        let first = self.left.len();
+        for (k, &constraint) in constraints.iter().enumerate() {
+            let node = first + k;
+            let header = constraint + 1;
+            self.left.push(if k == 0 {
+                first + constraints.len() - 1
+            } else {
+                node - 1
+            });
+            self.right.push(if k + 1 == constraints.len() {
+                first
+            } else {
+                node + 1
+            });
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            let last = self.up[header];
+            self.down[last] = node;
+            self.up[header] = node;
+            self.column.push(header);
+            self.size[header] += 1;
+            self.candidate.push(candidate);
+        }
+        first
+    }
+
+    /// Remove a column header and every row with a 1 in the column.
+    fn cover(&mut self, header: usize) {
+        let (left, right) = (self.left[header], self.right[header]);
+        self.right[left] = right;
+        self.left[right] = left;
+        let mut row = self.down[header];
+        while row != header {
+            let mut node = self.right[row];
+            while node != row {
+                let (up, down) = (self.up[node], self.down[node]);
+                self.down[up] = down;
+                self.up[down] = up;
+                self.size[self.column[node]] -= 1;
+                node = self.right[node];
+            }
+            row = self.down[row];
+        }
+    }
+
+    /// Undo `cover`, restoring the links in the reverse order.
+    fn uncover(&mut self, header: usize) {
+        let mut row = self.up[header];
+        while row != header {
+            let mut node = self.left[row];
+            while node != row {
+                let (up, down) = (self.up[node], self.down[node]);
+                self.down[up] = node;
+                self.up[down] = node;
+                self.size[self.column[node]] += 1;
+                node = self.left[node];
+            }
+            row = self.up[row];
+        }
+        let (left, right) = (self.left[header], self.right[header]);
+        self.right[left] = header;
+        self.left[right] = header;
+    }
+
+    /// Add a row to the solution by covering every column it has a 1 in.
+    fn select(&mut self, row: usize) {
+        let mut node = row;
+        loop {
+            self.cover(self.column[node]);
+            node = self.right[node];
+            if node == row {
+                break;
+            }
+        }
+    }
+
+    /// Check if the placement of a row keeps adjacent cells from holding consecutive
+    /// digits, when that rule applies.
+    fn fits(&self, row: usize) -> bool {
+        let (i, j, value) = self.candidate[row];
+        !self.nonconsecutive
+            || orthogonal_neighbors(i, j).all(|(r, c)| {
+                let other = self.values[r][c];
+                other == 0 || other.abs_diff(value) != 1
+            })
+    }
+
+    /// Count the rows left in a column that `fits` allows.
+    fn rows_left(&self, header: usize) -> usize {
+        if !self.nonconsecutive {
+            return self.size[header];
+        }
+        let mut count = 0;
+        let mut row = self.down[header];
+        while row != header {
+            count += usize::from(self.fits(row));
+            row = self.down[row];
+        }
+        count
+    }
+
+    /// Search for rows that cover the remaining columns, always branching on the
+    /// column with the fewest rows left that fit. Returns true with the rows in `selected`
+    /// if a solution was found.
+    fn search(&mut self, selected: &mut Vec<usize>, stats: &mut SolveStats) -> bool {
+        if self.right[0] == 0 {
+            return true;
+        }
+        let mut header = self.right[0];
+        let mut fewest = self.rows_left(header);
+        let mut next = self.right[header];
+        while next != 0 && fewest > 0 {
+            let rows = self.rows_left(next);
+            if rows < fewest {
+                header = next;
+                fewest = rows;
+            }
+            next = self.right[next];
+        }
+
+        self.cover(header);
+        let mut row = self.down[header];
+        while row != header {
+            if !self.fits(row) {
+                row = self.down[row];
+                continue;
+            }
+            let (i, j, value) = self.candidate[row];
+            self.values[i][j] = value;
+            selected.push(row);
+            stats.guesses += 1;
+            stats.max_depth = stats.max_depth.max(selected.len());
+            let mut node = self.right[row];
+            while node != row {
+                self.cover(self.column[node]);
+                node = self.right[node];
+            }
+            if self.search(selected, stats) {
+                return true;
+            }
+            let mut node = self.left[row];
+            while node != row {
+                self.uncover(self.column[node]);
+                node = self.left[node];
+            }
+            selected.pop();
+            self.values[i][j] = 0;
+            stats.backtracks += 1;
+            row = self.down[row];
+        }
+        self.uncover(header);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+    /// A puzzle that needs guesses with the plain solver
+    const GUESSING: &str =
+        ".....1..74......39.21...4..94.......1..9...85.5..3....8...4....6....8..2....25.6.";
+
+    fn grid(s: &str) -> SudokuGrid {
+        SudokuGrid::from_str_compact(s).unwrap()
+    }
+
+    #[test]
+    fn solves_puzzles() {
+        assert_eq!(solve(&grid(PUZZLE)).unwrap().to_compact_string(), SOLUTION);
+        let solution = solve(&grid(GUESSING)).unwrap();
+        assert!(solution.is_solution_of(&grid(GUESSING)));
+        assert!(solve(&SudokuGrid::default()).unwrap().is_valid());
+    }
+
+    #[test]
+    fn agrees_with_the_solver() {
+        let puzzle = grid(GUESSING);
+        let (solution, stats) = solve_with_stats(&puzzle);
+        assert_eq!(solution, crate::solver::Solver::from_grid(puzzle).solve());
+        assert!(stats.guesses >= stats.backtracks);
+        assert!(stats.max_depth <= 81);
+    }
+
+    #[test]
+    fn follows_the_diagonal_rules() {
+        let empty = SudokuGrid::default()
+            .with_variant(Variant::Diagonal)
+            .unwrap();
+        let solution = solve(&empty).unwrap();
+        assert!(solution.is_valid());
+        let diagonal: Vec<u8> = (0..9).map(|i| solution.at(i, i)).collect();
+        let anti: Vec<u8> = (0..9).map(|i| solution.at(i, 8 - i)).collect();
+        for values in [diagonal, anti] {
+            assert!((1..=9).all(|value| values.contains(&value)));
+        }
+    }
+
+    #[test]
+    fn reports_unsolvable_grids() {
+        // The 8s leave no place for an 8 in the first row
+        let mut puzzle = SudokuGrid::default();
+        for col in 0..7 {
+            assert!(puzzle.set(0, col, col as u8 + 1));
+        }
+        assert!(puzzle.set(4, 7, 8));
+        assert!(puzzle.set(7, 8, 8));
+        let (solution, stats) = solve_with_stats(&puzzle);
+        assert_eq!(solution, None);
+        assert_eq!(stats.guesses, stats.backtracks);
+
+        let mut locked = grid(PUZZLE);
+        locked.lock(0, 2);
+        assert_eq!(solve(&locked), None);
+    }
+
+    #[test]
+    fn follows_the_disjoint_groups_rules() {
+        let empty = SudokuGrid::default()
+            .with_variant(Variant::DisjointGroups)
+            .unwrap();
+        let solution = solve(&empty).unwrap();
+        assert!(solution.is_valid());
+        for position in 0..9 {
+            let group: Vec<u8> = (0..9)
+                .map(|square| {
+                    let row = (square / 3) * 3 + position / 3;
+                    let col = (square % 3) * 3 + position % 3;
+                    solution.at(row, col)
+                })
+                .collect();
+            assert!((1..=9).all(|value| group.contains(&value)));
+        }
+    }
+
+    #[test]
+    fn follows_the_nonconsecutive_rules() {
+        // The solution the solver finds for an empty nonconsecutive grid, with a few clues
+        let solution = grid(
+            "135279468468513792792846135246381579579624813813957246351792684684135927927468351",
+        )
+        .with_variant(Variant::NonConsecutive)
+        .unwrap();
+        let mut puzzle = solution.clone();
+        for (row, col) in (0..81).map(|k| (k / 9, k % 9)).filter(|k| k.0 % 2 == 1) {
+            puzzle.unset(row, col);
+        }
+        let found = solve(&puzzle).unwrap();
+        assert!(found.is_valid());
+        assert!(found.is_solution_of(&puzzle));
+        for (row, col, value) in found.iter_cells() {
+            for (i, j) in orthogonal_neighbors(row, col) {
+                assert_ne!(found.at(i, j).abs_diff(value), 1);
+            }
+        }
+    }
+}
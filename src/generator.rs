@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+
+use crate::{
+    input::{unit_cells, SudokuGrid},
+    solver::Solver,
+    transform::Transform,
+};
+
+/// A small deterministic pseudo-random number generator (SplitMix64).
+/// Generation only needs reproducible shuffles, not cryptographic randomness.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        mix64(self.0)
+    }
+
+    /// Shuffle a slice in place (Fisher-Yates)
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Scramble the bits of a number, as the output step of SplitMix64.
+/// Nearby inputs give unrelated outputs, so this can turn a counter into random numbers.
+pub(crate) fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// A Sudoku puzzle generator.
+///
+/// The generator first fills a complete grid at random, then removes clues one at a time
+/// in random order, keeping a clue whenever removing it would make the solution ambiguous.
+/// The same seed always produces the same puzzles.
+///
+/// Each uniqueness check gives up after a number of guesses, set with `with_guess_limit`,
+/// so that a pathological intermediate grid cannot stall generation. A clue is kept
+/// whenever the check gives up.
+pub struct Generator {
+    rng: Rng,
+    guess_limit: u64,
+}
+
+/// Number of guesses a uniqueness check may make by default before the clue is kept
+const DEFAULT_GUESS_LIMIT: u64 = 10_000;
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng(seed),
+            guess_limit: DEFAULT_GUESS_LIMIT,
+        }
+    }
+
+    /// Change the number of guesses each uniqueness check may make before giving up.
+    pub fn with_guess_limit(mut self, guess_limit: u64) -> Self {
+        self.guess_limit = guess_limit;
+        self
+    }
+
+    /// Generate a random complete grid.
+    pub fn solution(&mut self) -> SudokuGrid {
+        // The squares on the diagonal don't share rows or columns,
+        // so any permutation of the digits in each of them is consistent
+        let mut grid = SudokuGrid::default();
+        for square in [0, 4, 8] {
+            let mut digits = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+            self.rng.shuffle(&mut digits);
+            for ((row, col), value) in unit_cells(18 + square).zip(digits) {
+                // Safe to ignore because the digits are unique within the square
+                let _ = grid.set(row, col, value);
+            }
+        }
+        // A grid with only the diagonal squares filled always has a solution
+        Solver::from_grid(grid).solve().unwrap()
+    }
+
+    /// Generate a puzzle with a unique solution and as close to `clues` clues as possible.
+    /// The puzzle may have more clues if no more can be removed without losing uniqueness.
+    pub fn generate(&mut self, clues: usize) -> SudokuGrid {
+        let solution = self.solution();
+        self.remove_clues(solution, clues)
+    }
+
+    /// Generate a puzzle like `generate` whose solution is not in a blacklist, drawing new
+    /// solutions until one has a `fingerprint` that is not in `avoid`.
+    pub fn generate_avoiding(&mut self, clues: usize, avoid: &HashSet<u64>) -> SudokuGrid {
+        let solution = loop {
+            let solution = self.solution();
+            if !avoid.contains(&solution.fingerprint()) {
+                break solution;
+            }
+        };
+        self.remove_clues(solution, clues)
+    }
+
+    /// Generate an endless stream of puzzles like `generate`, skipping puzzles whose
+    /// `fingerprint` has already been produced, so every puzzle is distinct.
+    pub fn puzzles(&mut self, clues: usize) -> impl Iterator<Item = SudokuGrid> + '_ {
+        let mut seen = HashSet::new();
+        std::iter::repeat_with(move || self.generate(clues))
+            .filter(move |puzzle| seen.insert(puzzle.fingerprint()))
+    }
+
+    /// Generate a puzzle like `generate` whose clue pattern has a symmetry: clues are
+    /// removed together with their images under the symmetry, so the pattern maps onto itself.
+    /// The puzzle may have more clues than `generate` would give, since whole orbits of cells
+    /// have to be removable at once.
+    pub fn generate_symmetric(&mut self, clues: usize, symmetry: Symmetry) -> SudokuGrid {
+        let solution = self.solution();
+        self.remove_symmetric_clues(solution, clues, symmetry)
+    }
+
+    /// Remove clues from a complete grid in random order while the solution is known to stay unique,
+    /// until `clues` clues are left or no more can be removed.
+    fn remove_clues(&mut self, grid: SudokuGrid, clues: usize) -> SudokuGrid {
+        self.remove_symmetric_clues(grid, clues, Symmetry::None)
+    }
+
+    /// Remove clues like `remove_clues`, a whole orbit of the symmetry at a time.
+    /// Orbits that would leave fewer than `clues` clues are skipped.
+    fn remove_symmetric_clues(
+        &mut self,
+        mut grid: SudokuGrid,
+        clues: usize,
+        symmetry: Symmetry,
+    ) -> SudokuGrid {
+        let mut orbits = symmetry.orbits();
+        self.rng.shuffle(&mut orbits);
+
+        let mut filled = 81;
+        for orbit in orbits {
+            if filled <= clues {
+                break;
+            }
+            if filled - orbit.len() < clues {
+                continue;
+            }
+            let values: Vec<u8> = orbit.iter().map(|&(row, col)| grid.at(row, col)).collect();
+            for &(row, col) in orbit.iter() {
+                grid.unset(row, col);
+            }
+            let unique =
+                Solver::from_grid(grid.clone()).has_unique_solution_within(self.guess_limit);
+            if unique == Some(true) {
+                filled -= orbit.len();
+            } else {
+                for (&(row, col), &value) in orbit.iter().zip(values.iter()) {
+                    // Safe to ignore because the values were just removed from these cells
+                    let _ = grid.set(row, col, value);
+                }
+            }
+        }
+        grid
+    }
+}
+
+/// A symmetry that the clue pattern of a generated puzzle follows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    /// Clues are removed one at a time, with no symmetry
+    #[default]
+    None,
+    /// The pattern is the same after rotating by 180 degrees
+    Rotational180,
+    /// The pattern is the same after rotating by 90 degrees, and so by any multiple of it
+    Rotational90,
+    /// The pattern is the same after mirroring the rows, swapping top and bottom
+    HorizontalMirror,
+    /// The pattern is the same after mirroring the columns, swapping left and right
+    VerticalMirror,
+    /// The pattern is the same after reflecting across the main diagonal
+    Diagonal,
+}
+
+impl Symmetry {
+    /// Get the transforms that, with the identity, make up the symmetry group.
+    fn transforms(self) -> &'static [Transform] {
+        match self {
+            Symmetry::None => &[],
+            Symmetry::Rotational180 => &[Transform::Rotate180],
+            Symmetry::Rotational90 => &[
+                Transform::Rotate90,
+                Transform::Rotate180,
+                Transform::Rotate270,
+            ],
+            Symmetry::HorizontalMirror => &[Transform::FlipVertical],
+            Symmetry::VerticalMirror => &[Transform::FlipHorizontal],
+            Symmetry::Diagonal => &[Transform::Transpose],
+        }
+    }
+
+    /// Get the position of a cell under each transform of the symmetry group, other
+    /// than the identity.
+    pub fn images(self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.transforms()
+            .iter()
+            .map(move |transform| transform.map(row, col))
+    }
+
+    /// Split the cells of the grid into orbits, the sets of cells that the symmetry
+    /// maps onto each other, in row-major order of their first cell.
+    fn orbits(self) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = [[false; 9]; 9];
+        let mut orbits = Vec::new();
+        for (row, col) in (0..81).map(|k| (k / 9, k % 9)) {
+            if seen[row][col] {
+                continue;
+            }
+            let mut orbit = vec![(row, col)];
+            orbit.extend(self.images(row, col));
+            orbit.sort_unstable();
+            orbit.dedup();
+            for &(i, j) in orbit.iter() {
+                seen[i][j] = true;
+            }
+            orbits.push(orbit);
+        }
+        orbits
+    }
+}
+
+/// Make a puzzle with a unique solution out of a complete grid, with as close to `clues`
+/// clues as possible, like `Generator::generate` does with its random solutions.
+///
+/// The clues are removed in an order shuffled with `seed`. Without a seed, the RNG is
+/// seeded with the `fingerprint` of the solution, so the same solution always gives the
+/// same puzzle.
+///
+/// Panics if the grid is not a complete and valid solution.
+pub fn puzzle_from_solution(solution: &SudokuGrid, clues: usize, seed: Option<u64>) -> SudokuGrid {
+    assert!(
+        solution.empty_cells().next().is_none() && solution.is_valid(),
+        "the grid must be a complete solution"
+    );
+    let seed = seed.unwrap_or_else(|| solution.fingerprint());
+    Generator::new(seed).remove_clues(solution.clone(), clues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_solutions_are_complete() {
+        let solution = Generator::new(1).solution();
+        assert_eq!(solution.empty_cells().count(), 0);
+        assert!(solution.is_valid());
+    }
+
+    #[test]
+    fn generated_puzzles_have_a_unique_solution() {
+        for seed in 0..3 {
+            let puzzle = Generator::new(seed).generate(30);
+            assert!(
+                puzzle
+                    .iter_cells()
+                    .filter(|&(_, _, value)| value != 0)
+                    .count()
+                    >= 30
+            );
+            assert!(Solver::from_grid(puzzle).has_unique_solution());
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_puzzle() {
+        assert_eq!(
+            Generator::new(7).generate(30),
+            Generator::new(7).generate(30)
+        );
+        assert_ne!(Generator::new(7).solution(), Generator::new(8).solution());
+    }
+
+    #[test]
+    fn puzzles_from_a_solution_are_seeded_by_its_fingerprint() {
+        let solution = Generator::new(3).solution();
+        let puzzle = puzzle_from_solution(&solution, 28, None);
+        assert_eq!(puzzle, puzzle_from_solution(&solution, 28, None));
+        assert_eq!(
+            puzzle,
+            puzzle_from_solution(&solution, 28, Some(solution.fingerprint()))
+        );
+        assert_ne!(puzzle, puzzle_from_solution(&solution, 28, Some(1)));
+        assert!(solution.is_solution_of(&puzzle));
+        assert!(Solver::from_grid(puzzle).has_unique_solution());
+    }
+
+    #[test]
+    fn streamed_puzzles_are_distinct() {
+        let puzzles: Vec<SudokuGrid> = Generator::new(5).puzzles(40).take(4).collect();
+        let fingerprints: HashSet<u64> = puzzles.iter().map(SudokuGrid::fingerprint).collect();
+        assert_eq!(fingerprints.len(), 4);
+        assert_eq!(puzzles[0], Generator::new(5).generate(40));
+    }
+
+    #[test]
+    fn uniqueness_checks_are_bounded() {
+        // With no guesses allowed, no check can finish and every clue is kept
+        let solution = Generator::new(2).solution();
+        assert_eq!(Generator::new(2).with_guess_limit(0).generate(17), solution);
+
+        let puzzle = Generator::new(2).with_guess_limit(200).generate(17);
+        assert!(puzzle.empty_cells().count() > 0);
+        assert!(Solver::from_grid(puzzle).has_unique_solution());
+    }
+
+    #[test]
+    fn blacklisted_solutions_are_never_produced() {
+        // Blacklist the first solutions the seed would draw
+        let mut generator = Generator::new(4);
+        let avoid: HashSet<u64> = (0..3).map(|_| generator.solution().fingerprint()).collect();
+
+        let mut generator = Generator::new(4);
+        for _ in 0..3 {
+            let puzzle = generator.generate_avoiding(30, &avoid);
+            let solution = Solver::from_grid(puzzle).solve().unwrap();
+            assert!(!avoid.contains(&solution.fingerprint()));
+        }
+    }
+
+    #[test]
+    fn clue_patterns_follow_the_symmetry() {
+        for symmetry in [
+            Symmetry::None,
+            Symmetry::Rotational180,
+            Symmetry::Rotational90,
+            Symmetry::HorizontalMirror,
+            Symmetry::VerticalMirror,
+            Symmetry::Diagonal,
+        ] {
+            let puzzle = Generator::new(6).generate_symmetric(28, symmetry);
+            for (row, col, value) in puzzle.iter_cells() {
+                for (i, j) in symmetry.images(row, col) {
+                    assert_eq!(puzzle.at(i, j) != 0, value != 0, "{:?}", symmetry);
+                }
+            }
+            assert!(puzzle.empty_cells().count() > 0);
+            assert!(Solver::from_grid(puzzle).has_unique_solution());
+        }
+    }
+
+    #[test]
+    fn no_symmetry_generates_the_usual_puzzles() {
+        assert_eq!(
+            Generator::new(9).generate_symmetric(30, Symmetry::None),
+            Generator::new(9).generate(30)
+        );
+        assert_eq!(Symmetry::None.orbits().len(), 81);
+        assert_eq!(Symmetry::Rotational90.orbits().len(), 21);
+        assert_eq!(Symmetry::Diagonal.orbits().len(), 45);
+    }
+}
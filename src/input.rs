@@ -1,6 +1,10 @@
-use std::path::PathBuf;
+use std::{
+    fmt::Write,
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+};
 
-use anyhow::Context;
+use crate::{solver::Solver, transform::Transform};
 
 #[derive(Debug, Clone, Copy)]
 struct BitMask(u16);
@@ -23,36 +27,553 @@ impl BitMask {
     }
 }
 
+/// Index of the square containing each cell, i.e. `(row / 3) * 3 + (col / 3)`.
+/// Precomputed to keep the divisions out of the hot path.
+pub(crate) const SQUARE_OF: [[usize; 9]; 9] = {
+    let mut table = [[0; 9]; 9];
+    let mut row = 0;
+    while row < 9 {
+        let mut col = 0;
+        while col < 9 {
+            table[row][col] = (row / 3) * 3 + (col / 3);
+            col += 1;
+        }
+        row += 1;
+    }
+    table
+};
+
+/// Get the cells of a unit as `(row, col)`, where units 0 to 8 are the rows,
+/// 9 to 17 the columns, and 18 to 26 the squares, numbered like `SudokuGrid::square_mask`.
+/// Panics if the unit is not less than 27.
+pub fn unit_cells(unit: usize) -> impl Iterator<Item = (usize, usize)> + Clone {
+    assert!(unit < 27, "units are numbered from 0 to 26");
+    (0..9).map(move |k| match unit / 9 {
+        0 => (unit, k),
+        1 => (k, unit % 9),
+        _ => ((unit % 9) / 3 * 3 + k / 3, (unit % 9) % 3 * 3 + k % 3),
+    })
+}
+
+/// Format a cell position in the 1-based `R#C#` notation used in user-facing messages,
+/// with `R1C1` being the top-left cell. Rows and columns are 0-based everywhere else.
+pub fn cell_name(row: usize, col: usize) -> String {
+    format!("R{}C{}", row + 1, col + 1)
+}
+
+/// Parse a cell position in the 1-based `R#C#` notation produced by `cell_name`,
+/// returning the 0-based row and column. The notation is case-insensitive.
+/// Returns None if the text is not a valid cell position.
+pub fn parse_cell_name(name: &str) -> Option<(usize, usize)> {
+    let name = name.to_ascii_uppercase();
+    let (row, col) = name.strip_prefix('R')?.split_once('C')?;
+    let (row, col) = (row.parse::<usize>().ok()?, col.parse::<usize>().ok()?);
+    if (1..=9).contains(&row) && (1..=9).contains(&col) {
+        Some((row - 1, col - 1))
+    } else {
+        None
+    }
+}
+
+/// Parse the character of a cell, returning 0 for an empty cell.
+/// The line and column of the character are only used for the error.
+fn parse_cell(c: char, line: usize, col: usize) -> Result<u8, SudokuError> {
+    match c {
+        '.' | '0' | '_' => Ok(0),
+        '1'..='9' => {
+            // Safe to unwrap because we know the character is a digit
+            Ok(c.to_digit(10).unwrap() as u8)
+        }
+        _ => Err(SudokuError::InvalidChar { ch: c, line, col }),
+    }
+}
+
+/// A puzzle read from a SadMan Software `.sdk` file by `SudokuGrid::parse_sdk`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdkPuzzle {
+    pub grid: SudokuGrid,
+    /// The header fields in the order they appear, such as `('A', "author")` for `#A author`
+    pub metadata: Vec<(char, String)>,
+}
+
+/// Where to load a puzzle from.
+///
+/// All sources hold a grid in one of the formats accepted by `SudokuGrid::from_file`.
+#[derive(Debug, Clone)]
+pub enum PuzzleSource {
+    /// A file on disk
+    File(PathBuf),
+    /// The standard input of the process
+    Stdin,
+    /// The puzzle text itself
+    Inline(String),
+}
+
+impl PuzzleSource {
+    /// Load the puzzle from the source.
+    /// Returns an error if the source cannot be read or has invalid content.
+    pub fn load(&self) -> Result<SudokuGrid, SudokuError> {
+        match self {
+            PuzzleSource::File(path) => SudokuGrid::from_file(path),
+            PuzzleSource::Stdin => SudokuGrid::from_reader(std::io::stdin()),
+            PuzzleSource::Inline(input) => SudokuGrid::parse(input),
+        }
+    }
+}
+
+/// List the cells filled in by a solution as `(index, value)` pairs, where
+/// `index` is the row-major cell index `row * 9 + col`.
+/// Only cells that are empty in the puzzle are listed.
+/// Use `SudokuGrid::apply_delta` on the puzzle to get the solution back.
+pub fn solution_delta(puzzle: &SudokuGrid, solution: &SudokuGrid) -> Vec<(u8, u8)> {
+    let mut delta = Vec::new();
+    for i in 0..9 {
+        for j in 0..9 {
+            if puzzle.at(i, j) == 0 && solution.at(i, j) != 0 {
+                let index = SudokuGrid::index_of(i, j).unwrap();
+                delta.push((index as u8, solution.at(i, j)));
+            }
+        }
+    }
+    delta
+}
+
+/// A digit that cannot be placed in a cell because the cell already holds
+/// a different digit or the digit is used in the cell's row, column, or square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub row: usize,
+    pub col: usize,
+    pub value: u8,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "conflicting digit {} at {}",
+            self.value,
+            cell_name(self.row, self.col)
+        )
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// Two givens that together break a rule of the grid, such as the same digit twice in a row.
+/// The first cell comes before the second in row-major order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConflictPair {
+    pub first: Conflict,
+    pub second: Conflict,
+}
+
+impl std::fmt::Display for ConflictPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} and {} both contain {}",
+            cell_name(self.first.row, self.first.col),
+            cell_name(self.second.row, self.second.col),
+            self.first.value
+        )
+    }
+}
+
+/// The ways reading a puzzle can fail.
+/// Rows and columns of the grid are 0-based, and shown 1-based in the messages.
+/// Lines and columns of the input text are 1-based, counting every line of the input.
+#[derive(Debug, thiserror::Error)]
+pub enum SudokuError {
+    /// The 9-line format has more than 9 grid rows
+    #[error("Input has more than 9 rows")]
+    TooManyRows,
+    /// A row of the 9-line format has more than 9 cells
+    #[error("Line {line} has more than 9 digits")]
+    TooManyColumns { line: usize },
+    /// The 81-character format does not have exactly 81 cells
+    #[error("Expected 81 cells, found {0}")]
+    WrongCellCount(usize),
+    /// A character that is neither a digit nor an empty cell marker
+    #[error("Invalid character {ch:?} at line {line}, column {col}")]
+    InvalidChar { ch: char, line: usize, col: usize },
+    /// A numeric cell value greater than 9
+    #[error("Invalid value {value} at {}", cell_name(*.row, *.col))]
+    InvalidValue { value: u8, row: usize, col: usize },
+    /// The unused high nibble of the last byte of a packed grid is not 0
+    #[error("Invalid padding {0} in the last byte of a packed grid")]
+    InvalidPadding(u8),
+    /// A cell position outside of the 9x9 grid
+    #[error("Cell {} is outside the grid", cell_name(*.row, *.col))]
+    OutOfRange { row: usize, col: usize },
+    /// A digit that cannot be placed in a cell
+    #[error("conflicting digit {value} at {}", cell_name(*.row, *.col))]
+    ConflictingClue { row: usize, col: usize, value: u8 },
+    /// Givens that break the rules of the grid, such as the same digit given twice
+    /// in a row, column, or square, listing every pair of conflicting cells
+    #[error("Puzzle is invalid: {}", join_conflicts(.conflicts))]
+    InvalidPuzzle { conflicts: Vec<ConflictPair> },
+    /// A puzzle on one line of a multi-puzzle input is invalid
+    #[error("Invalid puzzle on line {line}")]
+    InvalidLine {
+        /// The 1-based line number
+        line: usize,
+        source: Box<SudokuError>,
+    },
+    /// A multi-puzzle input has no puzzle at the requested index
+    #[error("Puzzle index {index} is out of range, the input has {count} puzzles")]
+    IndexOutOfRange {
+        /// The 0-based index that was requested
+        index: usize,
+        /// The number of puzzles in the input
+        count: usize,
+    },
+    /// A file that should hold one puzzle holds none or several
+    #[error("Expected one puzzle, found {0}")]
+    PuzzleCount(usize),
+    /// A grid order outside of `sized::ORDERS`
+    #[error("Unsupported grid order {0}")]
+    UnsupportedOrder(usize),
+    /// A Samurai layout that does not have the shape of five overlapping grids
+    #[error("Invalid samurai layout: {0}")]
+    Layout(String),
+    /// A TOML or YAML document that does not describe a grid
+    #[error("Invalid document: {0}")]
+    Document(String),
+    /// The puzzle file could not be opened
+    #[error("Failed to read file {path:?}")]
+    File {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// An image could not be written
+    #[error("Failed to write image {path:?}")]
+    Image {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The input could not be read
+    #[error("Failed to read input")]
+    Io(#[from] std::io::Error),
+}
+
+/// Describe every conflict on one line, for the message of `SudokuError::InvalidPuzzle`.
+fn join_conflicts(conflicts: &[ConflictPair]) -> String {
+    let conflicts: Vec<String> = conflicts.iter().map(ConflictPair::to_string).collect();
+    conflicts.join(", ")
+}
+
+impl From<Conflict> for SudokuError {
+    fn from(conflict: Conflict) -> Self {
+        SudokuError::ConflictingClue {
+            row: conflict.row,
+            col: conflict.col,
+            value: conflict.value,
+        }
+    }
+}
+
+/// The rules a grid follows on top of the usual rows, columns, and squares.
+/// Variants can be combined into `Variants` with `|`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Classic Sudoku with no extra constraints
+    #[default]
+    Classic,
+    /// X-Sudoku, where each of the two main diagonals also contains every digit once
+    Diagonal,
+    /// Disjoint groups, where the cells at the same position within each square
+    /// (such as the nine top-left cells) also contain every digit once
+    DisjointGroups,
+    /// Nonconsecutive Sudoku, where orthogonally adjacent cells never hold consecutive digits
+    NonConsecutive,
+}
+
+impl Variant {
+    /// Get the bit of the variant in a `Variants` set, which is 0 for `Variant::Classic`
+    /// since its rules always apply.
+    fn bit(self) -> u8 {
+        match self {
+            Variant::Classic => 0,
+            Variant::Diagonal => 1,
+            Variant::DisjointGroups => 2,
+            Variant::NonConsecutive => 4,
+        }
+    }
+}
+
+/// A combination of variants whose rules all apply on top of the classic ones,
+/// such as `Variant::Diagonal | Variant::NonConsecutive`.
+/// The empty set, which is the default, is classic Sudoku.
+///
+/// A set compares equal to a single `Variant` when it holds exactly that variant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Variants(u8);
+
+impl Variants {
+    /// Check if the rules of a variant apply. The classic rules always do.
+    pub fn contains(self, variant: Variant) -> bool {
+        self.0 & variant.bit() == variant.bit()
+    }
+
+    /// Iterate over the variants of the set, not counting `Variant::Classic`.
+    pub fn iter(self) -> impl Iterator<Item = Variant> {
+        [
+            Variant::Diagonal,
+            Variant::DisjointGroups,
+            Variant::NonConsecutive,
+        ]
+        .into_iter()
+        .filter(move |&variant| self.contains(variant))
+    }
+}
+
+impl From<Variant> for Variants {
+    fn from(variant: Variant) -> Self {
+        Variants(variant.bit())
+    }
+}
+
+impl<V: Into<Variants>> std::ops::BitOr<V> for Variants {
+    type Output = Variants;
+
+    fn bitor(self, other: V) -> Variants {
+        Variants(self.0 | other.into().0)
+    }
+}
+
+impl<V: Into<Variants>> std::ops::BitOr<V> for Variant {
+    type Output = Variants;
+
+    fn bitor(self, other: V) -> Variants {
+        Variants::from(self) | other
+    }
+}
+
+impl PartialEq<Variant> for Variants {
+    fn eq(&self, other: &Variant) -> bool {
+        *self == Variants::from(*other)
+    }
+}
+
+impl FromIterator<Variant> for Variants {
+    fn from_iter<I: IntoIterator<Item = Variant>>(variants: I) -> Self {
+        variants
+            .into_iter()
+            .fold(Variants::default(), |set, variant| set | variant)
+    }
+}
+
+/// Get the cells directly above, below, left, and right of a cell that are inside the grid.
+pub(crate) fn orthogonal_neighbors(row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> {
+    [
+        (row.wrapping_sub(1), col),
+        (row + 1, col),
+        (row, col.wrapping_sub(1)),
+        (row, col + 1),
+    ]
+    .into_iter()
+    .filter(|&(i, j)| i < 9 && j < 9)
+}
+
+/// Number of units that variants can add: the two diagonals and the nine disjoint groups
+const EXTRA_UNITS: usize = 11;
+
 /// A Sudoku grid
 ///
 /// The grid is represented as a 9x9 matrix of cells.
 /// Each cell contains a digit from 1 to 9, or 0 if the cell is empty.
 /// The grid also keeps track of the digits in each row, column, and square
 /// to quickly check if a value can be set in a cell.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SudokuGrid {
     cells: [[u8; 9]; 9],
     rows: [BitMask; 9],
     cols: [BitMask; 9],
     squares: [BitMask; 9],
+    locked: [BitMask; 9],
+    variant: Variants,
+    /// Digits in the units added by the variant, indexed as in `extra_units_of`
+    extra_units: [BitMask; EXTRA_UNITS],
 }
 
 impl SudokuGrid {
+    fn empty() -> Self {
+        Self {
+            cells: [[0; 9]; 9],
+            rows: [BitMask::new(); 9],
+            cols: [BitMask::new(); 9],
+            squares: [BitMask::new(); 9],
+            locked: [BitMask::new(); 9],
+            variant: Variants(0),
+            extra_units: [BitMask::new(); EXTRA_UNITS],
+        }
+    }
+
+    /// Get the variants of Sudoku the grid follows.
+    pub fn variant(&self) -> Variants {
+        self.variant
+    }
+
+    /// Return a copy of the grid that follows the rules of a variant, or of every variant
+    /// of a `Variants` set, instead of its current ones.
+    /// Locked cells stay locked.
+    /// Returns `SudokuError::InvalidPuzzle` if the filled cells break the variant's rules.
+    pub fn with_variant(&self, variant: impl Into<Variants>) -> Result<SudokuGrid, SudokuError> {
+        let mut grid = Self::from_cells(self.cells, variant)?;
+        grid.locked = self.locked;
+        Ok(grid)
+    }
+
+    /// Build a grid of a variant from its cell values, with 0 for empty cells.
+    /// Returns `SudokuError::InvalidPuzzle` with every pair of conflicting cells
+    /// if the values break the rules of the variant.
+    pub(crate) fn from_cells(
+        cells: [[u8; 9]; 9],
+        variant: impl Into<Variants>,
+    ) -> Result<Self, SudokuError> {
+        let variant = variant.into();
+        // Only the cells and the variant are needed to find the conflicts
+        let unchecked = Self {
+            cells,
+            variant,
+            ..Self::empty()
+        };
+        let conflicts = unchecked.find_conflicts();
+        if !conflicts.is_empty() {
+            let given = |row, col| Conflict {
+                row,
+                col,
+                value: cells[row][col],
+            };
+            let conflicts = conflicts
+                .into_iter()
+                .map(|(r1, c1, r2, c2)| ConflictPair {
+                    first: given(r1, c1),
+                    second: given(r2, c2),
+                })
+                .collect();
+            return Err(SudokuError::InvalidPuzzle { conflicts });
+        }
+
+        let mut grid = Self::empty();
+        grid.variant = variant;
+        for (i, row) in cells.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                grid.set_given(i, j, value)?;
+            }
+        }
+        Ok(grid)
+    }
+
+    /// Get the units added by the variant that constrain a cell: 0 for the main diagonal and
+    /// 1 for the anti-diagonal in `Variant::Diagonal`, and `2 + p` for the cells at position
+    /// `p` of their squares in `Variant::DisjointGroups`, where `p` is in row-major order.
+    pub(crate) fn extra_units_of(&self, row: usize, col: usize) -> impl Iterator<Item = usize> {
+        let diagonal = self.variant.contains(Variant::Diagonal);
+        let disjoint = self.variant.contains(Variant::DisjointGroups);
+        [
+            (diagonal && row == col).then_some(0),
+            (diagonal && row + col == 8).then_some(1),
+            disjoint.then_some(2 + (row % 3) * 3 + col % 3),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Get the digits that `Variant::NonConsecutive` rules out of a cell as a bitmask:
+    /// the digits one above or below the values of its orthogonal neighbors.
+    /// Returns 0 if the grid does not follow `Variant::NonConsecutive`.
+    fn consecutive_mask(&self, row: usize, col: usize) -> u16 {
+        if !self.variant.contains(Variant::NonConsecutive) {
+            return 0;
+        }
+        orthogonal_neighbors(row, col).fold(0, |mask, (i, j)| match self.cells[i][j] {
+            0 => mask,
+            value => {
+                let bit = 1u16 << (value - 1);
+                mask | ((bit << 1 | bit >> 1) & 0x1ff)
+            }
+        })
+    }
+
+    /// Get the row-major index `row * 9 + col` of a cell.
+    /// Returns None if the row or column is out of range.
+    pub fn index_of(row: usize, col: usize) -> Option<usize> {
+        if row < 9 && col < 9 {
+            Some(row * 9 + col)
+        } else {
+            None
+        }
+    }
+
+    /// Get the `(row, col)` of a cell from its row-major index.
+    /// Returns None if the index is not less than 81.
+    pub fn coords_of(index: usize) -> Option<(usize, usize)> {
+        if index < 81 {
+            Some((index / 9, index % 9))
+        } else {
+            None
+        }
+    }
+
     /// Get the value of a cell in the grid
     #[inline]
     pub fn at(&self, row: usize, col: usize) -> u8 {
         self.cells[row][col]
     }
 
+    /// Iterate over every cell as `(row, col, value)` in row-major order,
+    /// with a value of 0 for empty cells.
+    pub fn iter_cells(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        self.cells.iter().enumerate().flat_map(|(row, values)| {
+            values
+                .iter()
+                .enumerate()
+                .map(move |(col, &value)| (row, col, value))
+        })
+    }
+
+    /// Iterate over the empty cells as `(row, col)` in row-major order.
+    pub fn empty_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter_cells()
+            .filter(|&(_, _, value)| value == 0)
+            .map(|(row, col, _)| (row, col))
+    }
+
+    /// Check if a value can be set in a cell without modifying the grid.
+    /// Returns false if the cell is out of range, already filled or locked, the value
+    /// is not a digit from 1 to 9, or the value is already used in the cell's row,
+    /// column, or square (or the units added by the variant, such as the diagonals of `Variant::Diagonal`),
+    /// or is consecutive to an orthogonal neighbor for `Variant::NonConsecutive`.
+    pub fn is_legal(&self, row: usize, col: usize, value: u8) -> bool {
+        if row >= 9 || col >= 9 || !(1..=9).contains(&value) || self.is_locked(row, col) {
+            return false;
+        }
+        self.candidates_mask(row, col) & (1 << (value - 1)) != 0
+    }
+
     /// Set the value of a cell in the grid.
     /// Returns true if the value was set successfully, false otherwise.
+    /// A locked cell, a cell that is already filled, and a value greater than 9 cannot be set.
     /// If the value was not set, the grid remains unchanged.
+    /// Setting a value of 0 is a no-op that returns true; use `unset` to clear a cell.
     #[must_use]
     pub fn set(&mut self, row: usize, col: usize, value: u8) -> bool {
-        let square = (row / 3) * 3 + (col / 3);
-        if self.rows[row].is_set(value - 1)
+        if value == 0 {
+            return true;
+        }
+        let square = SQUARE_OF[row][col];
+        if value > 9
+            || self.cells[row][col] != 0
+            || self.locked[row].is_set(col as u8)
+            || self.rows[row].is_set(value - 1)
             || self.cols[col].is_set(value - 1)
             || self.squares[square].is_set(value - 1)
+            || self
+                .extra_units_of(row, col)
+                .any(|unit| self.extra_units[unit].is_set(value - 1))
+            || self.consecutive_mask(row, col) & (1 << (value - 1)) != 0
         {
             return false;
         }
@@ -60,26 +581,278 @@ impl SudokuGrid {
         self.rows[row].set(value - 1);
         self.cols[col].set(value - 1);
         self.squares[square].set(value - 1);
+        for unit in self.extra_units_of(row, col) {
+            self.extra_units[unit].set(value - 1);
+        }
         true
     }
 
+    /// Set a clue while building a grid, where 0 leaves the cell empty.
+    /// Returns the conflict if the digit cannot be placed.
+    fn set_given(&mut self, row: usize, col: usize, value: u8) -> Result<(), Conflict> {
+        if self.set(row, col, value) {
+            Ok(())
+        } else {
+            Err(Conflict { row, col, value })
+        }
+    }
+
+    /// Return a copy of the grid with the value of a cell set.
+    /// Returns None if the value cannot be set, for example because the cell is already
+    /// filled, in which case no copy is returned.
+    /// The grid itself is never modified.
+    pub fn with_cell(&self, row: usize, col: usize, value: u8) -> Option<SudokuGrid> {
+        let mut grid = self.clone();
+        if grid.set(row, col, value) {
+            Some(grid)
+        } else {
+            None
+        }
+    }
+
+    /// Check if the grid has at least one solution.
+    pub fn is_solvable(&self) -> bool {
+        Solver::from_grid(self.clone()).solve().is_some()
+    }
+
+    /// Count the solutions of the grid, stopping once `limit` solutions have been found,
+    /// like `Solver::count_solutions` on a copy of the grid.
+    pub fn solution_count(&self, limit: usize) -> usize {
+        Solver::from_grid(self.clone()).count_solutions(limit)
+    }
+
+    /// Check if placing a value in an empty cell still leaves the grid solvable.
+    ///
+    /// A move can be legal, with the value not yet used in the cell's row, column,
+    /// or square, and still lead to a grid without solutions.
+    /// Returns false if the move is not legal or the grid has no solution with it.
+    pub fn is_part_of_some_solution(&self, cell: (usize, usize), value: u8) -> bool {
+        let (row, col) = cell;
+        self.is_legal(row, col, value)
+            && self
+                .with_cell(row, col, value)
+                .is_some_and(|grid| grid.is_solvable())
+    }
+
+    /// Return a copy of the grid with the `(index, value)` pairs produced by
+    /// `solution_delta` filled in.
+    /// Returns None if an index is out of range, its cell is already filled,
+    /// or a value cannot be set.
+    pub fn apply_delta(&self, delta: &[(u8, u8)]) -> Option<SudokuGrid> {
+        let mut grid = self.clone();
+        for &(index, value) in delta {
+            let (row, col) = Self::coords_of(index as usize)?;
+            if !grid.set(row, col, value) {
+                return None;
+            }
+        }
+        Some(grid)
+    }
+
+    /// Combine the filled cells of two grids into a new grid.
+    /// Returns the first conflict if the grids disagree on a cell or
+    /// their digits clash in a row, column, or square.
+    pub fn merge(&self, other: &SudokuGrid) -> Result<SudokuGrid, Conflict> {
+        let mut grid = self.clone();
+        for i in 0..9 {
+            for j in 0..9 {
+                let value = other.cells[i][j];
+                if value == 0 || value == grid.cells[i][j] {
+                    continue;
+                }
+                if grid.cells[i][j] != 0 || !grid.set(i, j, value) {
+                    return Err(Conflict {
+                        row: i,
+                        col: j,
+                        value,
+                    });
+                }
+            }
+        }
+        Ok(grid)
+    }
+
     /// Unset the value of a cell in the grid.
-    /// The grid remains unchanged if the cell was already empty.
+    /// The grid remains unchanged if the cell was already empty or is locked.
     pub fn unset(&mut self, row: usize, col: usize) {
         let value = self.cells[row][col];
-        let square = (row / 3) * 3 + (col / 3);
+        if value == 0 || self.locked[row].is_set(col as u8) {
+            return;
+        }
+        let square = SQUARE_OF[row][col];
         self.rows[row].clear(value - 1);
         self.cols[col].clear(value - 1);
         self.squares[square].clear(value - 1);
+        for unit in self.extra_units_of(row, col) {
+            self.extra_units[unit].clear(value - 1);
+        }
         self.cells[row][col] = 0;
     }
 
+    /// Get the digits that can still be placed in a cell, in increasing order.
+    /// Yields nothing for a filled or locked cell.
+    pub fn candidates(&self, row: usize, col: usize) -> impl Iterator<Item = u8> {
+        let mask = self.candidates_mask(row, col);
+        (1..=9).filter(move |value| mask & (1 << (value - 1)) != 0)
+    }
+
+    /// Find one cell whose value is forced, place it, and return what was placed.
+    ///
+    /// A naked single (a cell with only one candidate) is preferred, followed by a
+    /// hidden single (a digit with only one possible cell in a row, column, or square).
+    /// Returns None if no cell is forced.
+    pub fn place_one_forced(&mut self) -> Option<(usize, usize, u8)> {
+        let (row, col, value) = self
+            .find_naked_single()
+            .or_else(|| self.find_hidden_single())?;
+        if self.set(row, col, value) {
+            Some((row, col, value))
+        } else {
+            None
+        }
+    }
+
+    fn find_naked_single(&self) -> Option<(usize, usize, u8)> {
+        for i in 0..9 {
+            for j in 0..9 {
+                let mask = self.candidates_mask(i, j);
+                if mask.count_ones() == 1 {
+                    return Some((i, j, mask.trailing_zeros() as u8 + 1));
+                }
+            }
+        }
+        None
+    }
+
+    fn find_hidden_single(&self) -> Option<(usize, usize, u8)> {
+        for unit in 0..27 {
+            for value in 1..=9u8 {
+                let mut places = unit_cells(unit)
+                    .filter(|&(i, j)| self.candidates_mask(i, j) & (1 << (value - 1)) != 0);
+                if let (Some((i, j)), None) = (places.next(), places.next()) {
+                    return Some((i, j, value));
+                }
+            }
+        }
+        None
+    }
+
+    /// Lock a cell so that its value can no longer be changed by `set` or `unset`.
+    /// A locked empty cell stays empty and has no candidates, so a grid with one
+    /// has no solution.
+    pub fn lock(&mut self, row: usize, col: usize) {
+        self.locked[row].set(col as u8);
+    }
+
+    /// Unlock a cell so that its value can be changed again.
+    pub fn unlock(&mut self, row: usize, col: usize) {
+        self.locked[row].clear(col as u8);
+    }
+
+    /// Check if a cell is locked.
+    #[inline]
+    pub fn is_locked(&self, row: usize, col: usize) -> bool {
+        self.locked[row].is_set(col as u8)
+    }
+
+    /// Borrow the values of a row, with 0 for empty cells.
+    #[inline]
+    pub fn row_slice(&self, row: usize) -> &[u8; 9] {
+        &self.cells[row]
+    }
+
+    /// Get a copy of the values of a column, with 0 for empty cells.
+    /// Columns are not contiguous in memory, so they cannot be borrowed like rows.
+    pub fn col_values(&self, col: usize) -> [u8; 9] {
+        let mut values = [0; 9];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.cells[i][col];
+        }
+        values
+    }
+
+    /// Get the digits used in a row as a bitmask.
+    /// Bit `d - 1` is set if digit `d` is present in the row.
+    #[inline]
+    pub fn row_mask(&self, row: usize) -> u16 {
+        self.rows[row].0
+    }
+
+    /// Get the digits used in a column as a bitmask.
+    /// Bit `d - 1` is set if digit `d` is present in the column.
+    #[inline]
+    pub fn col_mask(&self, col: usize) -> u16 {
+        self.cols[col].0
+    }
+
+    /// Get the digits used in a square as a bitmask.
+    /// Squares are numbered 0 to 8 from left to right, top to bottom.
+    /// Bit `d - 1` is set if digit `d` is present in the square.
+    #[inline]
+    pub fn square_mask(&self, square: usize) -> u16 {
+        self.squares[square].0
+    }
+
+    /// Get a mask of the empty cells, where `true` marks an empty cell.
+    pub fn empty_mask(&self) -> [[bool; 9]; 9] {
+        self.cells.map(|row| row.map(|value| value == 0))
+    }
+
+    /// Get the number of filled cells in a row.
+    #[inline]
+    pub fn filled_in_row(&self, row: usize) -> usize {
+        self.rows[row].0.count_ones() as usize
+    }
+
+    /// Get the number of filled cells in a column.
+    #[inline]
+    pub fn filled_in_col(&self, col: usize) -> usize {
+        self.cols[col].0.count_ones() as usize
+    }
+
+    /// Get the number of filled cells in a square.
+    #[inline]
+    pub fn filled_in_square(&self, square: usize) -> usize {
+        self.squares[square].0.count_ones() as usize
+    }
+
+    /// Get the number of filled cells in each band of 3 rows, from top to bottom.
+    pub fn clues_per_band(&self) -> [usize; 3] {
+        std::array::from_fn(|band| (0..3).map(|k| self.filled_in_row(band * 3 + k)).sum())
+    }
+
+    /// Get the number of filled cells in each stack of 3 columns, from left to right.
+    pub fn clues_per_stack(&self) -> [usize; 3] {
+        std::array::from_fn(|stack| (0..3).map(|k| self.filled_in_col(stack * 3 + k)).sum())
+    }
+
+    /// Get the digits that can still be placed in a cell as a bitmask.
+    /// Bit `d - 1` is set if digit `d` is not present in the cell's row, column, or square
+    /// (or the units added by the variant, such as the diagonals of `Variant::Diagonal`),
+    /// and, for `Variant::NonConsecutive`, not one above or below an orthogonal neighbor.
+    /// Returns 0 for a filled or locked cell, since no value can be set in it.
+    #[inline]
+    pub fn candidates_mask(&self, row: usize, col: usize) -> u16 {
+        if self.cells[row][col] != 0 || self.is_locked(row, col) {
+            return 0;
+        }
+        let mut used = self.rows[row].0 | self.cols[col].0 | self.squares[SQUARE_OF[row][col]].0;
+        for unit in self.extra_units_of(row, col) {
+            used |= self.extra_units[unit].0;
+        }
+        used |= self.consecutive_mask(row, col);
+        !used & 0x1ff
+    }
+
     /// Create a new SudokuGrid from a file.
-    /// The file should contain 9 lines with 9 digits each.
+    /// The file should contain 9 lines with 9 digits each, or all 81 cells on a
+    /// single line as accepted by `from_str_compact`.
     /// Empty cells can be represented by 0, '.' or '_'.
+    /// Blank lines and lines starting with '#' are ignored.
     ///
     /// Example:
     /// ```text
+    /// # source: Wikipedia
     /// 53__7____
     /// 6__195___
     /// _98____6_
@@ -91,58 +864,556 @@ impl SudokuGrid {
     /// ____8__79
     /// ```
     ///
+    /// Files in the SadMan Software formats are recognized by their extension:
+    /// a `.sdk` file is parsed with `parse_sdk`, and a `.sdm` file, with one 81-character
+    /// puzzle per line, must hold exactly one puzzle. Files with several puzzles can be
+    /// read with `stream_from_reader` or `nth_from_reader` instead.
+    ///
     /// Returns an error if the file does not exist, cannot be read, or has invalid content
-    /// (e.g. more than 9 lines, more than 9 digits per line, invalid characters).
-    pub fn from_file(input: &PathBuf) -> anyhow::Result<Self> {
-        let input = std::fs::read_to_string(input)
-            .with_context(|| format!("Failed to read file {:?}", input))?;
+    /// (e.g. more than 9 rows, more than 9 digits per row, invalid characters,
+    /// or the same digit given twice in a row, column, or square),
+    /// and `SudokuError::PuzzleCount` if a `.sdm` file does not hold exactly one puzzle.
+    pub fn from_file(input: &PathBuf) -> Result<Self, SudokuError> {
+        let file = std::fs::File::open(input).map_err(|source| SudokuError::File {
+            path: input.clone(),
+            source,
+        })?;
+        let extension = input.extension().and_then(|extension| extension.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("sdk") => Ok(Self::parse_sdk(&std::io::read_to_string(file)?)?.grid),
+            Some("sdm") => {
+                let mut grids = Self::stream_from_reader(BufReader::new(file))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match grids.len() {
+                    1 => Ok(grids.remove(0)),
+                    count => Err(SudokuError::PuzzleCount(count)),
+                }
+            }
+            _ => Self::from_reader(file),
+        }
+    }
 
-        let mut grid = Self {
-            cells: [[0; 9]; 9],
-            rows: [BitMask::new(); 9],
-            cols: [BitMask::new(); 9],
-            squares: [BitMask::new(); 9],
-        };
+    /// Parse a puzzle in the SadMan Software `.sdk` format: header lines such as
+    /// `#A author` or `#D description`, followed by the grid in either format accepted
+    /// by `from_file`. Section lines such as `[Puzzle]` are skipped.
+    ///
+    /// Returns the grid along with the header fields, or an error if the grid is invalid.
+    pub fn parse_sdk(input: &str) -> Result<SdkPuzzle, SudokuError> {
+        let mut metadata = Vec::new();
+        let mut grid = String::new();
+        for line in input.lines() {
+            let trimmed = line.trim();
+            if let Some(header) = trimmed.strip_prefix('#') {
+                let mut chars = header.chars();
+                if let Some(field) = chars.next() {
+                    metadata.push((field, chars.as_str().trim().to_string()));
+                }
+            } else if !trimmed.starts_with('[') {
+                grid.push_str(line);
+                grid.push('\n');
+            }
+        }
+        Ok(SdkPuzzle {
+            grid: Self::parse(&grid)?,
+            metadata,
+        })
+    }
+
+    /// Create a new SudokuGrid from a reader, such as stdin,
+    /// holding the puzzle in either format accepted by `from_file`.
+    /// Returns an error if reading fails or the content is invalid.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, SudokuError> {
+        let input = std::io::read_to_string(reader)?;
+        Self::parse(&input)
+    }
+
+    /// Parse a grid in either format accepted by `from_file`.
+    /// The input is treated as the 81-character format if it has a single row of 81 characters.
+    fn parse(input: &str) -> Result<Self, SudokuError> {
+        let mut rows = Self::grid_rows(input);
+        match (rows.next(), rows.next()) {
+            (Some(row), None) if row.1.trim().chars().count() == 81 => {
+                Self::parse_compact(std::iter::once(row))
+            }
+            _ => Self::parse_lines(input),
+        }
+    }
+
+    /// Iterate over the lines of the input that hold grid rows along with their line numbers,
+    /// skipping blank lines and comment lines starting with '#'.
+    /// Only trailing whitespace is trimmed, so that columns still match the input.
+    fn grid_rows(input: &str) -> impl Iterator<Item = (usize, &str)> + Clone {
+        input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end()))
+            .filter(|(_, line)| {
+                let line = line.trim_start();
+                !line.is_empty() && !line.starts_with('#')
+            })
+    }
 
-        for (i, line) in input.lines().enumerate() {
+    /// Parse a grid in the 9-line format.
+    fn parse_lines(input: &str) -> Result<Self, SudokuError> {
+        let mut values = [[0; 9]; 9];
+
+        for (i, (line, text)) in Self::grid_rows(input).enumerate() {
             if i >= 9 {
-                return Err(anyhow::anyhow!("Input file has more than 9 lines"));
+                return Err(SudokuError::TooManyRows);
             }
-            for (j, c) in line.trim().chars().enumerate() {
+            let cells = text
+                .chars()
+                .enumerate()
+                .skip_while(|(_, c)| c.is_whitespace());
+            for (j, (col, c)) in cells.enumerate() {
                 if j >= 9 {
-                    return Err(anyhow::anyhow!("Line {} has more than 9 digits", i + 1));
-                }
-                let _ = grid.set(
-                    i,
-                    j,
-                    match c {
-                        '.' | '0' | '_' => 0,
-                        '1'..='9' => {
-                            // Safe to unwrap because we know the character is a digit
-                            c.to_digit(10).unwrap() as u8
-                        }
-                        _ => return Err(anyhow::anyhow!("Invalid character: {:?}", c)),
-                    },
-                );
+                    return Err(SudokuError::TooManyColumns { line });
+                }
+                values[i][j] = parse_cell(c, line, col + 1)?;
             }
         }
 
-        Ok(grid)
+        Self::from_cells(values, Variant::Classic)
     }
 
-    /// Check if the grid is valid.
-    /// A grid is valid if all rows, columns, and squares contain unique digits.
-    /// Returns true if the grid is valid, false otherwise.
-    pub fn is_valid(&self) -> bool {
-        for i in 0..9 {
-            if !self.is_valid_row(i) || !self.is_valid_col(i) || !self.is_valid_square(i) {
-                return false;
+    /// Pack the grid into 41 bytes, using 4 bits per cell.
+    ///
+    /// Cells are stored in row-major order, two per byte: cell `k` lives in
+    /// byte `k / 2`, in the low nibble when `k` is even and in the high nibble
+    /// when `k` is odd. Each nibble holds the digit, or 0 for an empty cell.
+    /// The high nibble of the last byte is always 0.
+    pub fn pack(&self) -> [u8; 41] {
+        let mut bytes = [0; 41];
+        for k in 0..81 {
+            let value = self.cells[k / 9][k % 9];
+            bytes[k / 2] |= value << (4 * (k % 2));
+        }
+        bytes
+    }
+
+    /// Get a 64-bit hash of the cells of the grid (FNV-1a over the 81 cell values).
+    ///
+    /// Unlike the standard library hashers, the fingerprint is the same on every platform
+    /// and in every version, so it can be stored to recognize grids later.
+    /// Like equality, it ignores the variant and which cells are locked.
+    pub fn fingerprint(&self) -> u64 {
+        self.cells
+            .iter()
+            .flatten()
+            .fold(0xcbf29ce484222325, |hash, &value| {
+                (hash ^ value as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Unpack a grid packed with `pack`.
+    /// Returns an error if a nibble is not a digit from 0 to 9, the padding nibble
+    /// of the last byte is not 0, or the digits conflict.
+    pub fn unpack(bytes: &[u8; 41]) -> Result<Self, SudokuError> {
+        if bytes[40] >> 4 != 0 {
+            return Err(SudokuError::InvalidPadding(bytes[40] >> 4));
+        }
+        let mut cells = [[0; 9]; 9];
+        for k in 0..81 {
+            let (i, j) = (k / 9, k % 9);
+            let value = (bytes[k / 2] >> (4 * (k % 2))) & 0xf;
+            if value > 9 {
+                return Err(SudokuError::InvalidValue {
+                    value,
+                    row: i,
+                    col: j,
+                });
             }
+            cells[i][j] = value;
         }
-        true
+        Self::from_cells(cells, Variant::Classic)
     }
 
-    fn is_valid_row(&self, row: usize) -> bool {
+    /// Build a grid from `(row, col, value)` givens, with 0-based coordinates.
+    /// Returns an error if a cell is outside the grid or a value is greater than 9,
+    /// and the first conflict if a cell is given two different values or a digit clashes
+    /// with its row, column, or square.
+    pub fn from_triples(
+        triples: impl IntoIterator<Item = (usize, usize, u8)>,
+    ) -> Result<Self, SudokuError> {
+        let mut grid = Self::empty();
+        for (row, col, value) in triples {
+            if row >= 9 || col >= 9 {
+                return Err(SudokuError::OutOfRange { row, col });
+            }
+            if value > 9 {
+                return Err(SudokuError::InvalidValue { value, row, col });
+            }
+            if grid.cells[row][col] != 0 && grid.cells[row][col] != value {
+                return Err(Conflict { row, col, value }.into());
+            }
+            if grid.cells[row][col] == 0 {
+                grid.set_given(row, col, value)?;
+            }
+        }
+        Ok(grid)
+    }
+
+    /// Lazily parse one SudokuGrid per line from a reader.
+    /// Each line holds all 81 cells in row-major order, using the same characters
+    /// as `from_file`. Blank lines are skipped.
+    ///
+    /// A line that cannot be read or parsed yields an error for that line only;
+    /// the following lines are still parsed.
+    pub fn stream_from_reader<R: BufRead>(
+        reader: R,
+    ) -> impl Iterator<Item = Result<SudokuGrid, SudokuError>> {
+        Self::stream_variant_from_reader(reader, Variant::Classic)
+    }
+
+    /// Lazily parse one SudokuGrid per line from a reader like `stream_from_reader`,
+    /// with every grid following the rules of a variant.
+    /// A line whose givens break the rules of the variant yields an error for that line.
+    pub fn stream_variant_from_reader<R: BufRead>(
+        reader: R,
+        variant: impl Into<Variants>,
+    ) -> impl Iterator<Item = Result<SudokuGrid, SudokuError>> {
+        let variant = variant.into();
+        reader.lines().enumerate().filter_map(move |(i, line)| {
+            let result = line.map_err(SudokuError::from).and_then(|line| {
+                if line.trim().is_empty() {
+                    return Ok(None);
+                }
+                let grid = Self::parse_compact(std::iter::once((i + 1, line.as_str())))?;
+                grid.with_variant(variant).map(Some)
+            });
+            result
+                .map_err(|err| SudokuError::InvalidLine {
+                    line: i + 1,
+                    source: Box::new(err),
+                })
+                .transpose()
+        })
+    }
+
+    /// Parse the puzzle at a 0-based index of a reader with one puzzle per line, in the
+    /// format of `stream_from_reader`, following the rules of a variant.
+    /// Blank lines are not counted, and the other puzzles are skipped without being parsed.
+    ///
+    /// Returns `SudokuError::IndexOutOfRange` with the number of puzzles if the input has
+    /// no puzzle at the index, and `SudokuError::InvalidLine` if that puzzle is invalid.
+    pub fn nth_from_reader<R: BufRead>(
+        reader: R,
+        index: usize,
+        variant: impl Into<Variants>,
+    ) -> Result<SudokuGrid, SudokuError> {
+        let mut count = 0;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if count == index {
+                return Self::parse_compact(std::iter::once((i + 1, line.as_str())))
+                    .and_then(|grid| grid.with_variant(variant))
+                    .map_err(|err| SudokuError::InvalidLine {
+                        line: i + 1,
+                        source: Box::new(err),
+                    });
+            }
+            count += 1;
+        }
+        Err(SudokuError::IndexOutOfRange { index, count })
+    }
+
+    /// Create a new SudokuGrid from the 81-character format used by most puzzle databases,
+    /// where the cells are listed in row-major order.
+    /// Empty cells can be represented by 0, '.' or '_'. Whitespace, including newlines, is ignored.
+    ///
+    /// Example:
+    /// ```text
+    /// 53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+    /// ```
+    ///
+    /// Returns an error if there are not exactly 81 cells, a character is invalid,
+    /// or the same digit is given twice in a row, column, or square.
+    pub fn from_str_compact(s: &str) -> Result<Self, SudokuError> {
+        Self::parse_compact(s.lines().enumerate().map(|(i, line)| (i + 1, line)))
+    }
+
+    /// Parse the 81-character format from lines of text along with their line numbers.
+    fn parse_compact<'a>(
+        lines: impl Iterator<Item = (usize, &'a str)> + Clone,
+    ) -> Result<Self, SudokuError> {
+        let cells = lines.flat_map(|(line, text)| {
+            text.chars()
+                .enumerate()
+                .filter(|(_, c)| !c.is_whitespace())
+                .map(move |(col, c)| (c, line, col + 1))
+        });
+        let len = cells.clone().count();
+        if len != 81 {
+            return Err(SudokuError::WrongCellCount(len));
+        }
+
+        let mut values = [[0; 9]; 9];
+        for (k, (c, line, col)) in cells.enumerate() {
+            values[k / 9][k % 9] = parse_cell(c, line, col)?;
+        }
+        Self::from_cells(values, Variant::Classic)
+    }
+
+    /// Render the grid in the 81-character format accepted by `from_str_compact`,
+    /// using '.' for empty cells.
+    pub fn to_compact_string(&self) -> String {
+        self.cells
+            .iter()
+            .flatten()
+            .map(|&value| match value {
+                0 => '.',
+                value => (b'0' + value) as char,
+            })
+            .collect()
+    }
+
+    /// Render the grid as 9 lines of 9 digits, using 0 for empty cells,
+    /// in the 9-line format accepted by `from_file`.
+    pub fn to_grid_lines(&self) -> String {
+        let mut output = String::new();
+        for row in self.cells.iter() {
+            for &value in row {
+                output.push((b'0' + value) as char);
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Display the grid like `Display`, marking the cells that are filled in `givens`
+    /// so that the clues of a puzzle stand out from the digits of its solution.
+    ///
+    /// Clues are shown in parentheses, or in bold when the `color` feature is enabled.
+    pub fn display_highlighted<'a>(
+        &'a self,
+        givens: &'a SudokuGrid,
+    ) -> impl std::fmt::Display + 'a {
+        Highlighted { grid: self, givens }
+    }
+
+    /// Display the grid as 9 lines of cells separated by spaces, without box borders,
+    /// using '_' for empty cells.
+    pub fn display_borderless(&self) -> impl std::fmt::Display + '_ {
+        Borderless(self)
+    }
+
+    /// Display the grid in a narrower layout than `Display`, with one character per cell,
+    /// `|` between squares and a line of dashes between bands of squares.
+    ///
+    /// Example:
+    /// ```text
+    /// 53_|_7_|___
+    /// 6__|195|___
+    /// _98|___|_6_
+    /// ---+---+---
+    /// ```
+    pub fn display_narrow(&self) -> impl std::fmt::Display + '_ {
+        Narrow(self)
+    }
+
+    /// Render the grid in the box layout of `Display`, marking every cell involved
+    /// in a conflict like `>5<`. Other cells are padded to keep the columns aligned.
+    pub fn display_conflicts(&self) -> String {
+        let conflicts = self.find_conflicts();
+        let is_conflicting = |i, j| {
+            conflicts
+                .iter()
+                .any(|&(r1, c1, r2, c2)| (r1, c1) == (i, j) || (r2, c2) == (i, j))
+        };
+
+        let mut output = String::new();
+        // Writing to a string cannot fail
+        let _ = write_boxes(&mut output, 3, "|", |f, i, j| {
+            let value = digit_char(self.cells[i][j]);
+            if is_conflicting(i, j) {
+                write!(f, ">{}<", value)
+            } else {
+                write!(f, " {} ", value)
+            }
+        });
+        output
+    }
+
+    /// Render the grid with keycap emoji for chat apps.
+    /// Empty cells are shown as a white square and squares are separated by a space.
+    /// The output has one line per row.
+    pub fn to_emoji(&self) -> String {
+        let mut output = String::new();
+        for i in 0..9 {
+            for j in 0..9 {
+                if j == 3 || j == 6 {
+                    output.push(' ');
+                }
+                match self.cells[i][j] {
+                    0 => output.push('\u{2b1c}'),
+                    value => {
+                        output.push((b'0' + value) as char);
+                        output.push_str("\u{fe0f}\u{20e3}");
+                    }
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Get the cells whose values differ between this grid and another,
+    /// in row-major order.
+    pub fn diff(&self, other: &SudokuGrid) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for i in 0..9 {
+            for j in 0..9 {
+                if self.cells[i][j] != other.cells[i][j] {
+                    cells.push((i, j));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Apply a symmetry transform to the grid, returning the transformed grid.
+    /// See `Transform::apply`.
+    pub fn apply_transform(&self, t: Transform) -> SudokuGrid {
+        t.apply(self)
+    }
+
+    /// Check if the grid is a complete solution of a puzzle: it has no empty cells,
+    /// is valid, and keeps every clue of the puzzle.
+    pub fn is_solution_of(&self, puzzle: &SudokuGrid) -> bool {
+        let complete = self.cells.iter().flatten().all(|&value| value != 0);
+        let keeps_clues = (0..9).all(|i| {
+            (0..9).all(|j| puzzle.cells[i][j] == 0 || puzzle.cells[i][j] == self.cells[i][j])
+        });
+        complete && keeps_clues && self.is_valid()
+    }
+
+    /// Check if the grid is valid.
+    /// A grid is valid if all rows, columns, and squares contain unique digits,
+    /// as well as the units added by the variant, such as both diagonals for `Variant::Diagonal`,
+    /// and no adjacent cells hold consecutive digits for `Variant::NonConsecutive`.
+    /// Returns true if the grid is valid, false otherwise.
+    pub fn is_valid(&self) -> bool {
+        for i in 0..9 {
+            if !self.is_valid_row(i) || !self.is_valid_col(i) || !self.is_valid_square(i) {
+                return false;
+            }
+        }
+        self.is_valid_extra_units() && self.is_valid_nonconsecutive()
+    }
+
+    /// Relabel the digits so that they are numbered 1, 2, 3, ... in the order
+    /// they first appear, reading the top row first and then the rest of the grid.
+    /// Blanks are skipped and remain empty.
+    /// Two grids that differ only by a relabeling of digits normalize to the same grid.
+    pub fn normalize_first_row(&self) -> SudokuGrid {
+        let mut labels = [0u8; 10];
+        let mut next = 1;
+        for row in self.cells.iter() {
+            for &value in row.iter() {
+                if value != 0 && labels[value as usize] == 0 {
+                    labels[value as usize] = next;
+                    next += 1;
+                }
+            }
+        }
+
+        let mut grid = Self::empty();
+        for i in 0..9 {
+            for j in 0..9 {
+                let value = self.cells[i][j];
+                if value != 0 {
+                    // Relabeling is a bijection, so it cannot introduce a conflict
+                    let _ = grid.set(i, j, labels[value as usize]);
+                }
+            }
+        }
+        grid
+    }
+
+    /// Panic with a readable dump of the grid unless it is a valid complete solution.
+    /// The panic message names the first conflict or empty cell found.
+    #[cfg(feature = "testing")]
+    #[track_caller]
+    pub fn assert_solved_or_panic(&self) {
+        let problem = if let Some(&(r1, c1, r2, c2)) = self.find_conflicts().first() {
+            format!(
+                "{} and {} both contain {}",
+                cell_name(r1, c1),
+                cell_name(r2, c2),
+                self.at(r1, c1)
+            )
+        } else if let Some(k) = (0..81).find(|&k| self.at(k / 9, k % 9) == 0) {
+            format!("{} is empty", cell_name(k / 9, k % 9))
+        } else {
+            return;
+        };
+        panic!("grid is not solved: {}\n{}", problem, self);
+    }
+
+    /// Check the grid for cells that share a row, column, or square with another cell
+    /// holding the same digit.
+    /// Returns the conflicting cells in row-major order, or `Ok(())` if there are none.
+    pub fn validate(&self) -> Result<(), Vec<Conflict>> {
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        for (r1, c1, r2, c2) in self.find_conflicts() {
+            for (row, col) in [(r1, c1), (r2, c2)] {
+                let conflict = Conflict {
+                    row,
+                    col,
+                    value: self.at(row, col),
+                };
+                if !conflicts.contains(&conflict) {
+                    conflicts.push(conflict);
+                }
+            }
+        }
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        conflicts.sort_by_key(|conflict| (conflict.row, conflict.col));
+        Err(conflicts)
+    }
+
+    /// Find the pairs of cells that share a row, column, or square (or a unit added by
+    /// the variant, such as a diagonal for `Variant::Diagonal`) and contain the same digit,
+    /// or, for `Variant::NonConsecutive`, that are orthogonally adjacent and contain
+    /// consecutive digits,
+    /// as `(row1, col1, row2, col2)` with the first cell before the second in row-major order.
+    /// A grid without conflicts can still have no solution.
+    pub fn find_conflicts(&self) -> Vec<(usize, usize, usize, usize)> {
+        let nonconsecutive = self.variant.contains(Variant::NonConsecutive);
+        let mut pairs = Vec::new();
+        for a in 0..81 {
+            let (r1, c1) = (a / 9, a % 9);
+            let value = self.at(r1, c1);
+            if value == 0 {
+                continue;
+            }
+            for b in a + 1..81 {
+                let (r2, c2) = (b / 9, b % 9);
+                let is_peer = r1 == r2
+                    || c1 == c2
+                    || SQUARE_OF[r1][c1] == SQUARE_OF[r2][c2]
+                    || self
+                        .extra_units_of(r1, c1)
+                        .any(|unit| self.extra_units_of(r2, c2).any(|other| other == unit));
+                let other = self.at(r2, c2);
+                let consecutive = nonconsecutive
+                    && other != 0
+                    && other.abs_diff(value) == 1
+                    && r1.abs_diff(r2) + c1.abs_diff(c2) == 1;
+                if (is_peer && other == value) || consecutive {
+                    pairs.push((r1, c1, r2, c2));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn is_valid_row(&self, row: usize) -> bool {
         let mut seen = [false; 9];
         for i in 0..9 {
             let value = self.at(row, i);
@@ -175,47 +1446,1308 @@ impl SudokuGrid {
     }
 
     fn is_valid_square(&self, square: usize) -> bool {
-        let start_row = (square / 3) * 3;
-        let start_col = (square % 3) * 3;
-
         let mut seen = [false; 9];
-        for i in 0..3 {
-            for j in 0..3 {
-                let value = self.at(start_row + i, start_col + j);
-                if value == 0 {
-                    continue;
-                }
-                let index = value as usize - 1;
-                if seen[index] {
+        for (i, j) in unit_cells(18 + square) {
+            let value = self.at(i, j);
+            if value == 0 {
+                continue;
+            }
+            let index = value as usize - 1;
+            if seen[index] {
+                return false;
+            }
+            seen[index] = true;
+        }
+        true
+    }
+
+    /// Check that no orthogonally adjacent cells hold consecutive digits,
+    /// if the grid follows `Variant::NonConsecutive`.
+    fn is_valid_nonconsecutive(&self) -> bool {
+        !self.variant.contains(Variant::NonConsecutive)
+            || self.iter_cells().all(|(row, col, value)| {
+                value == 0 || self.consecutive_mask(row, col) & (1 << (value - 1)) == 0
+            })
+    }
+
+    /// Check the units added by the variant for repeated digits.
+    fn is_valid_extra_units(&self) -> bool {
+        let mut seen = [[false; 9]; EXTRA_UNITS];
+        for (row, col, value) in self.iter_cells() {
+            if value == 0 {
+                continue;
+            }
+            for unit in self.extra_units_of(row, col) {
+                if std::mem::replace(&mut seen[unit][value as usize - 1], true) {
                     return false;
                 }
-                seen[index] = true;
             }
         }
         true
     }
 }
 
+/// Grids are equal when their cells are equal.
+/// The masks are derived from the cells, and neither the variant nor whether a cell is locked
+/// affects equality.
+impl PartialEq for SudokuGrid {
+    fn eq(&self, other: &Self) -> bool {
+        self.cells == other.cells
+    }
+}
+
+impl Eq for SudokuGrid {}
+
+/// Grids serialize as a flat sequence of the 81 cells in row-major order, with 0 for empty cells.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SudokuGrid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.cells.iter().flatten())
+    }
+}
+
+/// Deserialization rebuilds the masks through `set`, so sequences of the wrong length,
+/// values greater than 9, and conflicting digits are rejected.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SudokuGrid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, Unexpected};
+
+        let cells = Vec::<u8>::deserialize(deserializer)?;
+        if cells.len() != 81 {
+            return Err(D::Error::invalid_length(cells.len(), &"81 cells"));
+        }
+        let mut values = [[0; 9]; 9];
+        for (k, &value) in cells.iter().enumerate() {
+            if value > 9 {
+                return Err(D::Error::invalid_value(
+                    Unexpected::Unsigned(value as u64),
+                    &"a digit from 0 to 9",
+                ));
+            }
+            values[k / 9][k % 9] = value;
+        }
+        Self::from_cells(values, Variant::Classic).map_err(D::Error::custom)
+    }
+}
+
+/// A grid as a document with the 9 lines of `to_grid_lines` as row strings,
+/// for the TOML and YAML formats
+#[cfg(any(feature = "toml", feature = "yaml"))]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RowsDocument {
+    rows: Vec<String>,
+}
+
+#[cfg(any(feature = "toml", feature = "yaml"))]
+impl RowsDocument {
+    fn from_grid(grid: &SudokuGrid) -> Self {
+        Self {
+            rows: grid.to_grid_lines().lines().map(str::to_string).collect(),
+        }
+    }
+
+    /// Rebuild the grid from its rows, rejecting documents that are not 9 rows of 9 cells
+    /// and grids with conflicting digits.
+    fn into_grid(self) -> Result<SudokuGrid, SudokuError> {
+        if self.rows.len() != 9 || self.rows.iter().any(|row| row.chars().count() != 9) {
+            return Err(SudokuError::Document(
+                "expected 9 rows of 9 cells".to_string(),
+            ));
+        }
+        SudokuGrid::parse_lines(&self.rows.join("\n"))
+    }
+}
+
+/// Grids are stored in TOML as a `rows` array of 9 strings of 9 digits, with 0 for empty cells.
+#[cfg(feature = "toml")]
+impl SudokuGrid {
+    /// Serialize the grid as a TOML document.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&RowsDocument::from_grid(self)).expect("rows always serialize")
+    }
+
+    /// Parse a grid from a TOML document written by `to_toml`, rebuilding the masks.
+    pub fn from_toml(input: &str) -> Result<Self, SudokuError> {
+        toml::from_str::<RowsDocument>(input)
+            .map_err(|err| SudokuError::Document(err.to_string()))?
+            .into_grid()
+    }
+}
+
+/// Grids are stored in YAML as a `rows` list of 9 strings of 9 digits, with 0 for empty cells.
+#[cfg(feature = "yaml")]
+impl SudokuGrid {
+    /// Serialize the grid as a YAML document.
+    pub fn to_yaml(&self) -> String {
+        serde_norway::to_string(&RowsDocument::from_grid(self)).expect("rows always serialize")
+    }
+
+    /// Parse a grid from a YAML document written by `to_yaml`, rebuilding the masks.
+    pub fn from_yaml(input: &str) -> Result<Self, SudokuError> {
+        serde_norway::from_str::<RowsDocument>(input)
+            .map_err(|err| SudokuError::Document(err.to_string()))?
+            .into_grid()
+    }
+}
+
+impl std::str::FromStr for SudokuGrid {
+    type Err = SudokuError;
+
+    /// Parse a grid in either format accepted by `from_file`.
+    fn from_str(s: &str) -> Result<Self, SudokuError> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<&str> for SudokuGrid {
+    type Error = SudokuError;
+
+    /// Parse a grid in either format accepted by `from_file`.
+    fn try_from(input: &str) -> Result<Self, SudokuError> {
+        input.parse()
+    }
+}
+
+impl TryFrom<[[u8; 9]; 9]> for SudokuGrid {
+    type Error = SudokuError;
+
+    /// Build a grid from its rows, with 0 for empty cells.
+    /// Returns an error if a value is greater than 9 or the digits conflict.
+    fn try_from(cells: [[u8; 9]; 9]) -> Result<Self, SudokuError> {
+        for (i, row) in cells.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                if value > 9 {
+                    return Err(SudokuError::InvalidValue {
+                        value,
+                        row: i,
+                        col: j,
+                    });
+                }
+            }
+        }
+        Self::from_cells(cells, Variant::Classic)
+    }
+}
+
+impl Default for SudokuGrid {
+    /// Create an empty grid
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// Get the character shown for a cell value, with '_' for an empty cell.
+fn digit_char(value: u8) -> char {
+    match value {
+        0 => '_',
+        value => (b'0' + value) as char,
+    }
+}
+
+/// Write a grid in the box layout of `Display`, with a border around every square.
+///
+/// `cell` writes the cell at a row and column, taking `width` characters.
+/// Every square is closed by `close`, which is also used to size the borders.
+fn write_boxes<W: std::fmt::Write>(
+    f: &mut W,
+    width: usize,
+    close: &str,
+    mut cell: impl FnMut(&mut W, usize, usize) -> std::fmt::Result,
+) -> std::fmt::Result {
+    let dashes = "-".repeat(3 * width + close.len() - 1);
+    let border = format!("+{}+{}+{}+", dashes, dashes, dashes);
+    writeln!(f, "{}", border)?;
+    for i in 0..9 {
+        write!(f, "|")?;
+        for j in 0..9 {
+            cell(f, i, j)?;
+            if j % 3 == 2 {
+                write!(f, "{}", close)?;
+            }
+        }
+        writeln!(f)?;
+        if i % 3 == 2 {
+            writeln!(f, "{}", border)?;
+        }
+    }
+    Ok(())
+}
+
 impl std::fmt::Display for SudokuGrid {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(f, "+-------+-------+-------+")?;
-        for i in 0..9 {
-            write!(f, "|")?;
-            for j in 0..9 {
-                if self.cells[i][j] == 0 {
-                    write!(f, " _")?;
-                } else {
-                    write!(f, " {}", self.cells[i][j])?;
-                }
-                if j == 2 || j == 5 || j == 8 {
-                    write!(f, " |")?;
+        write_boxes(f, 2, " |", |f, i, j| {
+            write!(f, " {}", digit_char(self.cells[i][j]))
+        })
+    }
+}
+
+/// A grid displayed with the clues of a puzzle marked, see `SudokuGrid::display_highlighted`
+struct Highlighted<'a> {
+    grid: &'a SudokuGrid,
+    givens: &'a SudokuGrid,
+}
+
+impl std::fmt::Display for Highlighted<'_> {
+    #[cfg(feature = "color")]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write_boxes(f, 2, " |", |f, i, j| match self.grid.cells[i][j] {
+            value if value != 0 && self.givens.cells[i][j] != 0 => {
+                write!(f, " \x1b[1m{}\x1b[0m", value)
+            }
+            value => write!(f, " {}", digit_char(value)),
+        })
+    }
+
+    #[cfg(not(feature = "color"))]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Every cell is 3 characters wide to leave room for the parentheses
+        write_boxes(f, 3, "|", |f, i, j| match self.grid.cells[i][j] {
+            value if value != 0 && self.givens.cells[i][j] != 0 => write!(f, "({})", value),
+            value => write!(f, " {} ", digit_char(value)),
+        })
+    }
+}
+
+/// A grid displayed without box borders, see `SudokuGrid::display_borderless`
+struct Borderless<'a>(&'a SudokuGrid);
+
+impl std::fmt::Display for Borderless<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for row in self.0.cells.iter() {
+            for (j, &value) in row.iter().enumerate() {
+                if j > 0 {
+                    write!(f, " ")?;
                 }
+                write!(f, "{}", digit_char(value))?;
             }
             writeln!(f)?;
-            if i == 2 || i == 5 || i == 8 {
-                writeln!(f, "+-------+-------+-------+")?;
+        }
+        Ok(())
+    }
+}
+
+/// A grid displayed with one character per cell, see `SudokuGrid::display_narrow`
+struct Narrow<'a>(&'a SudokuGrid);
+
+impl std::fmt::Display for Narrow<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, row) in self.0.cells.iter().enumerate() {
+            if i == 3 || i == 6 {
+                writeln!(f, "---+---+---")?;
+            }
+            for (j, &value) in row.iter().enumerate() {
+                if j == 3 || j == 6 {
+                    write!(f, "|")?;
+                }
+                write!(f, "{}", digit_char(value))?;
             }
+            writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The puzzle from the Wikipedia article on Sudoku
+    const PUZZLE: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    /// The solution of `PUZZLE`
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    fn puzzle() -> SudokuGrid {
+        PUZZLE.parse().unwrap()
+    }
+
+    fn solution() -> SudokuGrid {
+        SOLUTION.parse().unwrap()
+    }
+
+    /// Build a grid from the 81-character format without checking its givens,
+    /// since conflicting grids cannot be built through the public API
+    fn unchecked(puzzle: &str) -> SudokuGrid {
+        let mut cells = [[0; 9]; 9];
+        for (k, c) in puzzle.chars().enumerate() {
+            cells[k / 9][k % 9] = c.to_digit(10).unwrap_or(0) as u8;
+        }
+        SudokuGrid {
+            cells,
+            ..SudokuGrid::empty()
+        }
+    }
+
+    #[test]
+    fn empty_grid_loads_without_panicking() {
+        let grid: SudokuGrid = ".".repeat(81).parse().unwrap();
+        assert_eq!(grid, SudokuGrid::default());
+        let grid: SudokuGrid = "_________\n".repeat(9).parse().unwrap();
+        assert_eq!(grid.empty_cells().count(), 81);
+    }
+
+    #[test]
+    fn set_and_unset_empty_values_are_no_ops() {
+        let mut grid = SudokuGrid::default();
+        assert!(grid.set(4, 4, 0));
+        grid.unset(4, 4);
+        assert_eq!(grid, SudokuGrid::default());
+        assert_eq!(grid.candidates_mask(4, 4), 0x1ff);
+    }
+
+    #[test]
+    fn set_rejects_values_greater_than_9() {
+        let mut grid = SudokuGrid::default();
+        assert!(!grid.set(0, 0, 10));
+        assert!(!grid.set(0, 0, 17));
+        assert!(!grid.set(0, 0, 255));
+        assert_eq!(grid.at(0, 0), 0);
+        assert_eq!(grid.row_mask(0), 0);
+    }
+
+    #[test]
+    fn set_rejects_filled_cells() {
+        let mut grid = puzzle();
+        assert!(!grid.set(0, 0, 1));
+        assert!(!grid.set(0, 0, 5));
+        assert_eq!(grid.at(0, 0), 5);
+        assert_eq!(grid.row_mask(0) & 1, 0);
+    }
+
+    #[test]
+    fn with_cell_leaves_the_original_unchanged() {
+        let grid = puzzle();
+        let next = grid.with_cell(0, 2, 4).unwrap();
+        assert_eq!(next.at(0, 2), 4);
+        assert_eq!(grid.at(0, 2), 0);
+        assert_eq!(grid, puzzle());
+        assert!(grid.with_cell(0, 2, 5).is_none());
+    }
+
+    #[test]
+    fn with_cell_rejects_filled_cells() {
+        let grid = puzzle();
+        assert!(grid.with_cell(0, 0, 1).is_none());
+        assert!(grid.with_cell(0, 0, 5).is_none());
+    }
+
+    #[test]
+    fn solution_delta_round_trips() {
+        let puzzle = puzzle();
+        let solution = Solver::from_grid(puzzle.clone()).solve().unwrap();
+        let delta = solution_delta(&puzzle, &solution);
+        assert_eq!(delta.len(), puzzle.empty_cells().count());
+        assert_eq!(puzzle.apply_delta(&delta), Some(solution));
+    }
+
+    #[test]
+    fn apply_delta_rejects_filled_cells() {
+        let puzzle = puzzle();
+        assert!(puzzle.apply_delta(&[(0, 1)]).is_none());
+        assert!(puzzle.apply_delta(&[(0, 5)]).is_none());
+        assert!(puzzle.apply_delta(&[(81, 1)]).is_none());
+    }
+
+    #[test]
+    fn locked_cells_cannot_be_changed() {
+        let mut grid = puzzle();
+        grid.lock(0, 0);
+        grid.lock(0, 2);
+        assert!(grid.is_locked(0, 0));
+        grid.unset(0, 0);
+        assert_eq!(grid.at(0, 0), 5);
+        assert!(!grid.set(0, 2, 4));
+        assert!(!grid.is_legal(0, 2, 4));
+        assert_eq!(grid.candidates_mask(0, 2), 0);
+
+        grid.unlock(0, 2);
+        assert!(!grid.is_locked(0, 2));
+        assert!(grid.set(0, 2, 4));
+    }
+
+    #[test]
+    fn locked_empty_cells_are_not_forced() {
+        // R5C5 is a naked single of the puzzle
+        let mut grid = puzzle();
+        assert_eq!(grid.candidates(4, 4).collect::<Vec<_>>(), [5]);
+        grid.lock(4, 4);
+        while let Some((row, col, _)) = grid.place_one_forced() {
+            assert_ne!((row, col), (4, 4));
+        }
+        assert_eq!(grid.at(4, 4), 0);
+        assert!(Solver::from_grid(grid).solve().is_none());
+    }
+
+    #[test]
+    fn from_triples_builds_a_grid() {
+        let grid = SudokuGrid::from_triples(vec![(0, 0, 5), (8, 8, 9), (4, 4, 0)]).unwrap();
+        assert_eq!(grid.at(0, 0), 5);
+        assert_eq!(grid.at(8, 8), 9);
+        assert_eq!(
+            grid.iter_cells()
+                .filter(|&(_, _, value)| value != 0)
+                .count(),
+            2
+        );
+        // Repeating a given is not a conflict
+        assert!(SudokuGrid::from_triples(vec![(0, 0, 5), (0, 0, 5)]).is_ok());
+    }
+
+    #[test]
+    fn from_triples_rejects_conflicts() {
+        let err = SudokuGrid::from_triples(vec![(0, 0, 5), (0, 8, 5)]).unwrap_err();
+        assert!(matches!(
+            err,
+            SudokuError::ConflictingClue {
+                row: 0,
+                col: 8,
+                value: 5
+            }
+        ));
+        let err = SudokuGrid::from_triples(vec![(0, 0, 5), (0, 0, 6)]).unwrap_err();
+        assert!(matches!(
+            err,
+            SudokuError::ConflictingClue { row: 0, col: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn from_triples_rejects_cells_outside_the_grid() {
+        let err = SudokuGrid::from_triples(vec![(9, 0, 1)]).unwrap_err();
+        assert!(matches!(err, SudokuError::OutOfRange { row: 9, col: 0 }));
+        assert_eq!(err.to_string(), "Cell R10C1 is outside the grid");
+        let err = SudokuGrid::from_triples(vec![(0, 0, 10)]).unwrap_err();
+        assert!(matches!(err, SudokuError::InvalidValue { value: 10, .. }));
+    }
+
+    #[test]
+    fn parse_errors_report_lines_of_the_input() {
+        let input = "# comment\n\n53__7____\n6__195___\n_98x___6_\n";
+        let err = input.parse::<SudokuGrid>().unwrap_err();
+        assert!(matches!(
+            err,
+            SudokuError::InvalidChar {
+                ch: 'x',
+                line: 5,
+                col: 4
+            }
+        ));
+        assert_eq!(err.to_string(), "Invalid character 'x' at line 5, column 4");
+
+        let input = "# comment\n53__7____\n6__195____\n";
+        let err = input.parse::<SudokuGrid>().unwrap_err();
+        assert!(matches!(err, SudokuError::TooManyColumns { line: 3 }));
+
+        let err = "_\n".repeat(10).parse::<SudokuGrid>().unwrap_err();
+        assert!(matches!(err, SudokuError::TooManyRows));
+    }
+
+    #[test]
+    fn compact_parse_errors_report_lines_of_the_input() {
+        let input = format!("# comment\n{}x", &PUZZLE[..80]);
+        let err = input.parse::<SudokuGrid>().unwrap_err();
+        assert!(matches!(
+            err,
+            SudokuError::InvalidChar {
+                ch: 'x',
+                line: 2,
+                col: 81
+            }
+        ));
+        let err = SudokuGrid::from_str_compact(&PUZZLE[..80]).unwrap_err();
+        assert!(matches!(err, SudokuError::WrongCellCount(80)));
+    }
+
+    #[test]
+    fn missing_files_are_io_errors() {
+        let err = SudokuGrid::from_file(&PathBuf::from("does/not/exist.txt")).unwrap_err();
+        assert!(matches!(err, SudokuError::File { .. }));
+    }
+
+    #[test]
+    fn invalid_puzzles_list_every_conflict() {
+        let input =
+            "55..7....5..19.....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+        let err = input.parse::<SudokuGrid>().unwrap_err();
+        let SudokuError::InvalidPuzzle { conflicts } = &err else {
+            panic!("unexpected error {err}");
+        };
+        let cells: Vec<_> = conflicts
+            .iter()
+            .map(|pair| {
+                (
+                    pair.first.row,
+                    pair.first.col,
+                    pair.second.row,
+                    pair.second.col,
+                )
+            })
+            .collect();
+        assert_eq!(cells, [(0, 0, 0, 1), (0, 0, 1, 0), (0, 1, 1, 0)]);
+        assert_eq!(
+            err.to_string(),
+            "Puzzle is invalid: R1C1 and R1C2 both contain 5, \
+             R1C1 and R2C1 both contain 5, R1C2 and R2C1 both contain 5"
+        );
+    }
+
+    #[test]
+    fn square_table_matches_the_formula() {
+        for (row, squares) in SQUARE_OF.iter().enumerate() {
+            for (col, &square) in squares.iter().enumerate() {
+                assert_eq!(square, (row / 3) * 3 + col / 3);
+            }
+        }
+    }
+
+    #[test]
+    fn unit_cells_cover_rows_columns_and_squares() {
+        assert_eq!(
+            unit_cells(2).collect::<Vec<_>>(),
+            (0..9).map(|k| (2, k)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            unit_cells(11).collect::<Vec<_>>(),
+            (0..9).map(|k| (k, 2)).collect::<Vec<_>>()
+        );
+        for square in 0..9 {
+            assert!(unit_cells(18 + square).all(|(row, col)| SQUARE_OF[row][col] == square));
+        }
+    }
+
+    #[test]
+    fn highlighted_display_marks_the_givens() {
+        let puzzle = puzzle();
+        let solution = Solver::from_grid(puzzle.clone()).solve().unwrap();
+        let output = solution.display_highlighted(&puzzle).to_string();
+        let first = output.lines().nth(1).unwrap();
+        if cfg!(feature = "color") {
+            assert_eq!(
+                first,
+                "| \x1b[1m5\x1b[0m \x1b[1m3\x1b[0m 4 | 6 \x1b[1m7\x1b[0m 8 | 9 1 2 |"
+            );
+        } else {
+            assert_eq!(first, "|(5)(3) 4 | 6 (7) 8 | 9  1  2 |");
+        }
+        assert_eq!(output.lines().count(), 13);
+        // Nothing is marked without givens
+        let plain = solution
+            .display_highlighted(&SudokuGrid::default())
+            .to_string();
+        assert!(!plain.contains('(') && !plain.contains('\x1b'));
+    }
+
+    #[test]
+    fn borderless_display_separates_cells_with_spaces() {
+        let output = puzzle().display_borderless().to_string();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "5 3 _ _ 7 _ _ _ _");
+        assert_eq!(lines[8], "_ _ _ _ 8 _ _ 7 9");
+    }
+
+    #[test]
+    fn normalize_first_row_relabels_digits_in_order() {
+        let grid = puzzle();
+        let normalized = grid.normalize_first_row();
+        assert_eq!(&normalized.row_slice(0)[..5], &[1, 2, 0, 0, 3]);
+        assert_eq!(normalized.empty_mask(), grid.empty_mask());
+
+        let relabeled = grid.apply_transform(Transform::Relabel([9, 8, 7, 6, 5, 4, 3, 2, 1]));
+        assert_ne!(relabeled, grid);
+        assert_eq!(relabeled.normalize_first_row(), normalized);
+    }
+
+    #[test]
+    fn cells_are_named_in_one_based_notation() {
+        assert_eq!(cell_name(0, 0), "R1C1");
+        assert_eq!(cell_name(2, 4), "R3C5");
+        assert_eq!(parse_cell_name("R3C5"), Some((2, 4)));
+        assert_eq!(parse_cell_name("r9c9"), Some((8, 8)));
+        assert_eq!(parse_cell_name("R0C1"), None);
+        assert_eq!(parse_cell_name("R10C1"), None);
+        assert_eq!(parse_cell_name("C1R1"), None);
+
+        let conflict = Conflict {
+            row: 2,
+            col: 4,
+            value: 7,
+        };
+        assert_eq!(conflict.to_string(), "conflicting digit 7 at R3C5");
+        assert_eq!(
+            SudokuError::from(conflict).to_string(),
+            "conflicting digit 7 at R3C5"
+        );
+    }
+
+    #[test]
+    fn unit_masks_hold_the_used_digits() {
+        let grid = puzzle();
+        let mask = |digits: &[u16]| digits.iter().map(|d| 1 << (d - 1)).sum::<u16>();
+        assert_eq!(grid.row_mask(0), mask(&[3, 5, 7]));
+        assert_eq!(grid.col_mask(0), mask(&[4, 5, 6, 7, 8]));
+        assert_eq!(grid.square_mask(0), mask(&[3, 5, 6, 8, 9]));
+        assert_eq!(grid.square_mask(8), mask(&[2, 5, 7, 8, 9]));
+        assert_eq!(SudokuGrid::default().row_mask(4), 0);
+    }
+
+    #[test]
+    fn stream_from_reader_parses_one_puzzle_per_line() {
+        let input = format!("{PUZZLE}\n\n  \nnot a puzzle\n{}\n", ".".repeat(81));
+        let results: Vec<_> = SudokuGrid::stream_from_reader(std::io::Cursor::new(input)).collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &puzzle());
+        assert!(matches!(
+            results[1],
+            Err(SudokuError::InvalidLine { line: 4, .. })
+        ));
+        assert_eq!(results[2].as_ref().unwrap(), &SudokuGrid::default());
+    }
+
+    #[test]
+    fn puzzle_sources_load_the_same_grid() {
+        let file = PuzzleSource::File(PathBuf::from("input/test1.txt"))
+            .load()
+            .unwrap();
+        let text = std::fs::read_to_string("input/test1.txt").unwrap();
+        assert_eq!(PuzzleSource::Inline(text).load().unwrap(), file);
+        assert_eq!(
+            PuzzleSource::Inline(PUZZLE.to_string()).load().unwrap(),
+            puzzle()
+        );
+        assert!(PuzzleSource::File(PathBuf::from("input/missing.txt"))
+            .load()
+            .is_err());
+    }
+
+    #[test]
+    fn emoji_rendering_has_nine_rows() {
+        let output = puzzle().to_emoji();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].starts_with("5\u{fe0f}\u{20e3}3\u{fe0f}\u{20e3}\u{2b1c} "));
+        assert_eq!(lines[0].matches('\u{2b1c}').count(), 6);
+    }
+
+    #[test]
+    fn diff_lists_the_cells_that_differ() {
+        let puzzle = puzzle();
+        assert!(puzzle.diff(&puzzle).is_empty());
+        let next = puzzle
+            .with_cell(0, 2, 4)
+            .unwrap()
+            .with_cell(8, 0, 3)
+            .unwrap();
+        assert_eq!(puzzle.diff(&next), [(0, 2), (8, 0)]);
+    }
+
+    #[test]
+    fn is_legal_checks_without_modifying_the_grid() {
+        let grid = puzzle();
+        let legal: Vec<u8> = (0..=10).filter(|&v| grid.is_legal(0, 2, v)).collect();
+        assert_eq!(legal, [1, 2, 4]);
+        assert!(grid.is_legal(0, 2, solution().at(0, 2)));
+        assert!(!grid.is_legal(0, 0, 5));
+        assert!(!grid.is_legal(9, 0, 1));
+        assert!(!grid.is_legal(0, 9, 1));
+        assert_eq!(grid, puzzle());
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn assert_solved_or_panic_accepts_a_solution() {
+        solution().assert_solved_or_panic();
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    #[should_panic(expected = "grid is not solved: R1C3 is empty")]
+    fn assert_solved_or_panic_names_the_first_empty_cell() {
+        puzzle().assert_solved_or_panic();
+    }
+
+    #[test]
+    fn merge_combines_filled_cells() {
+        let top = SudokuGrid::from_triples([(0, 2, 4), (1, 1, 7)]).unwrap();
+        let merged = puzzle().merge(&top).unwrap();
+        assert_eq!(merged.at(0, 2), 4);
+        assert_eq!(merged.at(1, 1), 7);
+        assert_eq!(merged.at(0, 0), 5);
+        assert_eq!(puzzle().merge(&solution()).unwrap(), solution());
+    }
+
+    #[test]
+    fn merge_reports_the_first_conflict() {
+        let disagree = SudokuGrid::from_triples([(0, 0, 1)]).unwrap();
+        assert_eq!(
+            puzzle().merge(&disagree),
+            Err(Conflict {
+                row: 0,
+                col: 0,
+                value: 1
+            })
+        );
+        let clash = SudokuGrid::from_triples([(0, 2, 9)]).unwrap();
+        assert_eq!(
+            puzzle().merge(&clash),
+            Err(Conflict {
+                row: 0,
+                col: 2,
+                value: 9
+            })
+        );
+    }
+
+    #[test]
+    fn pack_round_trips() {
+        let bytes = puzzle().pack();
+        assert_eq!(bytes[0], 0x35);
+        assert_eq!(bytes[40] >> 4, 0);
+        assert_eq!(SudokuGrid::unpack(&bytes).unwrap(), puzzle());
+        assert_eq!(SudokuGrid::unpack(&[0; 41]).unwrap(), SudokuGrid::default());
+    }
+
+    #[test]
+    fn unpack_rejects_invalid_nibbles() {
+        let mut bytes = [0; 41];
+        bytes[1] = 0xa0;
+        assert!(matches!(
+            SudokuGrid::unpack(&bytes),
+            Err(SudokuError::InvalidValue {
+                value: 10,
+                row: 0,
+                col: 3
+            })
+        ));
+        bytes[1] = 0x11;
+        assert!(matches!(
+            SudokuGrid::unpack(&bytes),
+            Err(SudokuError::InvalidPuzzle { .. })
+        ));
+        bytes[1] = 0;
+        bytes[40] = 0x30;
+        assert!(matches!(
+            SudokuGrid::unpack(&bytes),
+            Err(SudokuError::InvalidPadding(3))
+        ));
+    }
+
+    #[test]
+    fn rows_and_columns_hold_the_cell_values() {
+        let grid = puzzle();
+        assert_eq!(grid.row_slice(0), &[5, 3, 0, 0, 7, 0, 0, 0, 0]);
+        assert_eq!(grid.col_values(0), [5, 6, 0, 8, 4, 7, 0, 0, 0]);
+        assert_eq!(grid.col_values(8), [0, 0, 0, 3, 1, 6, 0, 5, 9]);
+    }
+
+    #[test]
+    fn filled_cells_are_counted_per_unit() {
+        let grid = puzzle();
+        assert_eq!(grid.filled_in_row(0), 3);
+        assert_eq!(grid.filled_in_col(0), 5);
+        assert_eq!(grid.filled_in_square(0), 5);
+        assert_eq!(grid.filled_in_square(4), 4);
+        assert_eq!(solution().filled_in_row(8), 9);
+        assert_eq!(SudokuGrid::default().filled_in_col(3), 0);
+    }
+
+    #[test]
+    fn narrow_display_is_narrower_than_display() {
+        let narrow = puzzle().display_narrow().to_string();
+        let lines: Vec<&str> = narrow.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "53_|_7_|___");
+        assert_eq!(lines[3], "---+---+---");
+        assert_eq!(lines[10], "___|_8_|_79");
+        let wide = puzzle().to_string();
+        let widest = |s: &str| s.lines().map(|line| line.chars().count()).max();
+        assert!(widest(&narrow) < widest(&wide));
+    }
+
+    #[test]
+    fn validate_lists_each_conflicting_cell_once() {
+        assert_eq!(puzzle().validate(), Ok(()));
+        assert_eq!(solution().validate(), Ok(()));
+
+        let grid = unchecked(
+            "55..7....5..19.....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        );
+        let conflict = |row, col| Conflict { row, col, value: 5 };
+        assert_eq!(
+            grid.validate(),
+            Err(vec![conflict(0, 0), conflict(0, 1), conflict(1, 0)])
+        );
+        assert!(!grid.is_valid());
+    }
+
+    #[test]
+    fn place_one_forced_places_a_single_value() {
+        let mut grid = puzzle();
+        let (row, col, value) = grid.place_one_forced().unwrap();
+        assert_eq!(grid.at(row, col), value);
+        assert_eq!(value, solution().at(row, col));
+        assert_eq!(
+            grid.empty_cells().count(),
+            puzzle().empty_cells().count() - 1
+        );
+
+        while grid.place_one_forced().is_some() {}
+        assert_eq!(grid, solution());
+        assert_eq!(grid.place_one_forced(), None);
+    }
+
+    #[test]
+    fn place_one_forced_finds_hidden_singles() {
+        // No cell has a single candidate, but 9 only fits in R1C9 of the top row
+        let mut grid =
+            SudokuGrid::from_triples([(1, 0, 9), (2, 3, 9), (3, 6, 9), (6, 7, 9)]).unwrap();
+        assert_eq!(grid.place_one_forced(), Some((0, 8, 9)));
+        assert_eq!(grid.at(0, 8), 9);
+    }
+
+    #[test]
+    fn conflicting_givens_are_rejected() {
+        let mut cells = [[0; 9]; 9];
+        assert_eq!(SudokuGrid::try_from(cells).unwrap(), SudokuGrid::default());
+        cells[0][0] = 5;
+        cells[8][8] = 5;
+        let grid = SudokuGrid::try_from(cells).unwrap();
+        assert!(matches!(
+            grid.with_variant(Variant::Diagonal),
+            Err(SudokuError::InvalidPuzzle { .. })
+        ));
+        cells[4][0] = 5;
+        let err = SudokuGrid::try_from(cells).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Puzzle is invalid: R1C1 and R5C1 both contain 5"
+        );
+    }
+
+    #[test]
+    fn display_conflicts_marks_the_conflicting_cells() {
+        let grid = unchecked(
+            "55..7....5..19.....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        );
+        let output = grid.display_conflicts();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "+---------+---------+---------+");
+        assert_eq!(lines[1], "|>5<>5< _ | _  7  _ | _  _  _ |");
+        assert_eq!(lines[2], "|>5< _  _ | 1  9  _ | _  _  _ |");
+        assert_eq!(lines[3], "| _  9  8 | _  _  _ | _  6  _ |");
+        assert!(!puzzle().display_conflicts().contains('>'));
+    }
+
+    #[test]
+    fn compact_format_ignores_whitespace() {
+        let spaced: String = PUZZLE
+            .as_bytes()
+            .chunks(9)
+            .map(|row| format!("  {}\n", std::str::from_utf8(row).unwrap()))
+            .collect();
+        assert_eq!(SudokuGrid::from_str_compact(&spaced).unwrap(), puzzle());
+        assert_eq!(
+            SudokuGrid::from_str_compact(&PUZZLE.replace('.', "0")).unwrap(),
+            puzzle()
+        );
+        assert!(matches!(
+            SudokuGrid::from_str_compact(&PUZZLE[1..]),
+            Err(SudokuError::WrongCellCount(80))
+        ));
+        assert!(matches!(
+            SudokuGrid::from_str_compact(&format!("{PUZZLE}1")),
+            Err(SudokuError::WrongCellCount(82))
+        ));
+    }
+
+    #[test]
+    fn readers_detect_the_compact_format() {
+        let single_line = format!("{PUZZLE}\n");
+        assert_eq!(
+            SudokuGrid::from_reader(single_line.as_bytes()).unwrap(),
+            puzzle()
+        );
+        assert_eq!(
+            SudokuGrid::from_reader(puzzle().to_grid_lines().as_bytes()).unwrap(),
+            puzzle()
+        );
+    }
+
+    #[test]
+    fn compact_strings_round_trip() {
+        assert_eq!(puzzle().to_compact_string(), PUZZLE);
+        assert_eq!(solution().to_compact_string(), SOLUTION);
+        assert_eq!(SudokuGrid::default().to_compact_string(), ".".repeat(81));
+        let parsed: SudokuGrid = puzzle().to_compact_string().parse().unwrap();
+        assert_eq!(parsed, puzzle());
+    }
+
+    #[test]
+    fn multi_line_and_compact_text_parse_the_same() {
+        let lines = "\
+53__7____
+6__195___
+_98____6_
+8___6___3
+4__8_3__1
+7___2___6
+_6____28_
+___419__5
+____8__79
+";
+        let multi_line: SudokuGrid = lines.parse().unwrap();
+        assert_eq!(multi_line, puzzle());
+        assert_eq!(multi_line.to_compact_string(), PUZZLE);
+    }
+
+    #[test]
+    fn empty_mask_marks_the_empty_cells() {
+        let grid = puzzle();
+        let mask = grid.empty_mask();
+        let empty = mask.iter().flatten().filter(|&&empty| empty).count();
+        assert_eq!(empty, grid.empty_cells().count());
+        assert_eq!(empty, 51);
+        assert!(!mask[0][0] && mask[0][2]);
+        assert_eq!(SudokuGrid::default().empty_mask(), [[true; 9]; 9]);
+    }
+
+    #[test]
+    fn candidates_exclude_digits_of_the_peers() {
+        let grid = puzzle();
+        assert_eq!(grid.candidates(0, 2).collect::<Vec<_>>(), [1, 2, 4]);
+        assert_eq!(grid.candidates(4, 4).collect::<Vec<_>>(), [5]);
+        assert_eq!(grid.candidates(0, 0).count(), 0);
+        assert_eq!(grid.candidates_mask(0, 2), 0b1011);
+        assert_eq!(
+            SudokuGrid::default().candidates(8, 8).collect::<Vec<_>>(),
+            (1..=9).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn clones_compare_by_their_cells() {
+        let original = puzzle();
+        let mut copy = original.clone();
+        assert_eq!(copy, original);
+        assert!(copy.set(0, 2, 4));
+        assert_ne!(copy, original);
+        copy.unset(0, 2);
+        assert_eq!(copy, original);
+        copy.lock(0, 2);
+        assert_eq!(copy, original);
+        assert_eq!(copy.candidates_mask(1, 1), original.candidates_mask(1, 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trips_as_a_flat_array() {
+        let json = serde_json::to_string(&puzzle()).unwrap();
+        assert!(json.starts_with("[5,3,0,0,7,0,"));
+        assert_eq!(serde_json::from_str::<SudokuGrid>(&json).unwrap(), puzzle());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_with_invalid_cells_is_rejected() {
+        let mut cells = vec![0u8; 81];
+        assert!(
+            serde_json::from_str::<SudokuGrid>(&serde_json::to_string(&cells).unwrap()).is_ok()
+        );
+        cells[0] = 10;
+        let err = serde_json::from_str::<SudokuGrid>(&serde_json::to_string(&cells).unwrap());
+        assert!(err.unwrap_err().to_string().contains("a digit from 0 to 9"));
+        cells[0] = 5;
+        cells[1] = 5;
+        let err = serde_json::from_str::<SudokuGrid>(&serde_json::to_string(&cells).unwrap());
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("R1C1 and R1C2 both contain 5"));
+        let err = serde_json::from_str::<SudokuGrid>(&serde_json::to_string(&cells[..80]).unwrap());
+        assert!(err.unwrap_err().to_string().contains("81 cells"));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trips_as_row_strings() {
+        let toml = puzzle().to_toml();
+        assert!(toml.starts_with("rows = [\"530070000\", \"600195000\","));
+        let grid = SudokuGrid::from_toml(&toml).unwrap();
+        assert_eq!(grid, puzzle());
+        assert_eq!(grid.candidates_mask(0, 2), puzzle().candidates_mask(0, 2));
+
+        let conflicting = toml.replacen("530070000", "550070000", 1);
+        assert!(matches!(
+            SudokuGrid::from_toml(&conflicting),
+            Err(SudokuError::InvalidPuzzle { .. })
+        ));
+        let short = toml.replacen("530070000", "53007000", 1);
+        assert!(matches!(
+            SudokuGrid::from_toml(&short),
+            Err(SudokuError::Document(_))
+        ));
+        assert!(SudokuGrid::from_toml("rows = 5").is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_round_trips_as_row_strings() {
+        let yaml = puzzle().to_yaml();
+        assert!(yaml.starts_with("rows:\n- '530070000'\n- '600195000'\n"));
+        let grid = SudokuGrid::from_yaml(&yaml).unwrap();
+        assert_eq!(grid, puzzle());
+        assert_eq!(grid.candidates_mask(4, 4), puzzle().candidates_mask(4, 4));
+
+        let conflicting = yaml.replacen("530070000", "550070000", 1);
+        assert!(matches!(
+            SudokuGrid::from_yaml(&conflicting),
+            Err(SudokuError::InvalidPuzzle { .. })
+        ));
+        let missing = yaml.replacen("- '530070000'\n", "", 1);
+        assert!(matches!(
+            SudokuGrid::from_yaml(&missing),
+            Err(SudokuError::Document(_))
+        ));
+    }
+
+    #[test]
+    fn indices_and_coordinates_convert_both_ways() {
+        assert_eq!(SudokuGrid::index_of(0, 0), Some(0));
+        assert_eq!(SudokuGrid::index_of(8, 8), Some(80));
+        assert_eq!(SudokuGrid::index_of(9, 0), None);
+        assert_eq!(SudokuGrid::coords_of(80), Some((8, 8)));
+        assert_eq!(SudokuGrid::coords_of(81), None);
+        for k in 0..81 {
+            let (row, col) = SudokuGrid::coords_of(k).unwrap();
+            assert_eq!(SudokuGrid::index_of(row, col), Some(k));
+        }
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_ignored() {
+        let input = format!(
+            "# source: Wikipedia\n\n{}\n# end of the first band\n\n{}",
+            puzzle().to_grid_lines()[..30].trim_end(),
+            &puzzle().to_grid_lines()[30..]
+        );
+        assert_eq!(input.parse::<SudokuGrid>().unwrap(), puzzle());
+        let compact = format!("# one line\n\n{PUZZLE}\n");
+        assert_eq!(
+            SudokuGrid::from_reader(compact.as_bytes()).unwrap(),
+            puzzle()
+        );
+    }
+
+    #[test]
+    fn clues_are_counted_per_band_and_stack() {
+        assert_eq!(puzzle().clues_per_band(), [10, 10, 10]);
+        assert_eq!(puzzle().clues_per_stack(), [9, 12, 9]);
+        assert_eq!(solution().clues_per_band(), [27; 3]);
+        assert_eq!(SudokuGrid::default().clues_per_stack(), [0; 3]);
+    }
+
+    #[test]
+    fn grid_lines_round_trip() {
+        let lines = puzzle().to_grid_lines();
+        assert_eq!(lines.lines().count(), 9);
+        assert_eq!(lines.lines().next(), Some("530070000"));
+        assert_eq!(lines.parse::<SudokuGrid>().unwrap(), puzzle());
+        assert_eq!(
+            SudokuGrid::from_reader(solution().to_grid_lines().as_bytes()).unwrap(),
+            solution()
+        );
+    }
+
+    #[test]
+    fn solutions_keep_the_clues_of_the_puzzle() {
+        assert!(solution().is_solution_of(&puzzle()));
+        assert!(solution().is_solution_of(&SudokuGrid::default()));
+        assert!(!puzzle().is_solution_of(&puzzle()));
+        let other = SudokuGrid::from_triples([(0, 2, 1)]).unwrap();
+        assert!(!solution().is_solution_of(&other));
+        let broken = unchecked(&SOLUTION.replacen('5', "3", 1));
+        assert!(!broken.is_solution_of(&SudokuGrid::default()));
+    }
+
+    #[test]
+    fn cells_are_iterated_in_row_major_order() {
+        let grid = puzzle();
+        let cells: Vec<_> = grid.iter_cells().collect();
+        assert_eq!(cells.len(), 81);
+        assert_eq!(cells[0], (0, 0, 5));
+        assert_eq!(cells[2], (0, 2, 0));
+        assert_eq!(cells[80], (8, 8, 9));
+        let empty: Vec<_> = grid.empty_cells().take(3).collect();
+        assert_eq!(empty, [(0, 2), (0, 3), (0, 5)]);
+        assert_eq!(solution().empty_cells().next(), None);
+    }
+
+    #[test]
+    fn legal_moves_can_still_be_dead_ends() {
+        let grid = puzzle();
+        assert!(grid.is_legal(0, 2, 1));
+        assert!(!grid.is_part_of_some_solution((0, 2), 1));
+        assert!(grid.is_part_of_some_solution((0, 2), 4));
+        assert!(!grid.is_part_of_some_solution((0, 2), 5));
+        assert!(!grid.is_part_of_some_solution((0, 0), 5));
+    }
+
+    #[test]
+    fn solvable_grids_have_a_solution() {
+        assert!(puzzle().is_solvable());
+        assert!(solution().is_solvable());
+        assert!(SudokuGrid::default().is_solvable());
+        assert!(!puzzle().with_cell(0, 2, 1).unwrap().is_solvable());
+    }
+
+    #[test]
+    fn fingerprints_follow_the_cells() {
+        assert_eq!(puzzle().fingerprint(), puzzle().fingerprint());
+        assert_ne!(puzzle().fingerprint(), solution().fingerprint());
+        let diagonal = puzzle().with_variant(Variant::Diagonal).unwrap();
+        assert_eq!(diagonal.fingerprint(), puzzle().fingerprint());
+    }
+
+    #[test]
+    fn solution_counts_match_the_solver() {
+        let two = SudokuGrid::from_str_compact(
+            "5346789126721953..1983425678597614234268537917139248569615372..287419635345286179",
+        )
+        .unwrap();
+        for grid in [puzzle(), solution(), two.clone(), SudokuGrid::default()] {
+            for limit in [1, 2, 5] {
+                assert_eq!(
+                    grid.solution_count(limit),
+                    Solver::from_grid(grid.clone()).count_solutions(limit)
+                );
+            }
+        }
+        assert_eq!(puzzle().solution_count(5), 1);
+        assert_eq!(two.solution_count(5), 2);
+        assert_eq!(SudokuGrid::default().solution_count(5), 5);
+        assert_eq!(puzzle().with_cell(0, 2, 1).unwrap().solution_count(5), 0);
+    }
+
+    #[test]
+    fn puzzles_are_picked_by_index() {
+        let input = format!("{}\n\n{}\n55{}\n", PUZZLE, SOLUTION, &SOLUTION[2..]);
+        let pick = |index| SudokuGrid::nth_from_reader(input.as_bytes(), index, Variant::Classic);
+        assert_eq!(pick(1).unwrap(), solution());
+        assert!(matches!(
+            pick(2),
+            Err(SudokuError::InvalidLine { line: 4, .. })
+        ));
+        assert!(matches!(
+            pick(3),
+            Err(SudokuError::IndexOutOfRange { index: 3, count: 3 })
+        ));
+    }
+
+    #[test]
+    fn sdk_files_have_a_header_and_a_grid() {
+        let input = "\
+#A Wikipedia
+#D The example puzzle
+#L Easy
+[Puzzle]
+53..7....
+6..195...
+.98....6.
+8...6...3
+4..8.3..1
+7...2...6
+.6....28.
+...419..5
+....8..79
+";
+        let sdk = SudokuGrid::parse_sdk(input).unwrap();
+        assert_eq!(sdk.grid, puzzle());
+        assert_eq!(
+            sdk.metadata,
+            [
+                ('A', "Wikipedia".to_string()),
+                ('D', "The example puzzle".to_string()),
+                ('L', "Easy".to_string())
+            ]
+        );
+        assert!(SudokuGrid::parse_sdk("#A nobody\n55\n").is_err());
+    }
+
+    #[test]
+    fn sadman_files_are_loaded_by_extension() {
+        let dir = std::env::temp_dir();
+        let sdm = dir.join(format!("sudoku-rs-{}-puzzles.sdm", std::process::id()));
+        std::fs::write(&sdm, format!("{}\n", PUZZLE)).unwrap();
+        assert_eq!(SudokuGrid::from_file(&sdm).unwrap(), puzzle());
+
+        // The later puzzles of a file are not dropped silently
+        std::fs::write(&sdm, format!("{}\n{}\n", PUZZLE, SOLUTION)).unwrap();
+        assert!(matches!(
+            SudokuGrid::from_file(&sdm),
+            Err(SudokuError::PuzzleCount(2))
+        ));
+        let all: Vec<SudokuGrid> =
+            SudokuGrid::stream_from_reader(BufReader::new(std::fs::File::open(&sdm).unwrap()))
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(all, [puzzle(), solution()]);
+
+        let sdk = dir.join(format!("sudoku-rs-{}-puzzle.SDK", std::process::id()));
+        std::fs::write(&sdk, format!("[Puzzle]\n#A someone\n{}\n", SOLUTION)).unwrap();
+        assert_eq!(SudokuGrid::from_file(&sdk).unwrap(), solution());
+        std::fs::remove_file(sdm).unwrap();
+        std::fs::remove_file(sdk).unwrap();
+    }
+
+    #[test]
+    fn variants_combine_into_sets() {
+        let both = Variant::Diagonal | Variant::NonConsecutive;
+        assert!(both.contains(Variant::Diagonal) && both.contains(Variant::NonConsecutive));
+        assert!(both.contains(Variant::Classic));
+        assert!(!both.contains(Variant::DisjointGroups));
+        assert_eq!(
+            both.iter().collect::<Vec<_>>(),
+            [Variant::Diagonal, Variant::NonConsecutive]
+        );
+        assert_eq!(Variants::default(), Variant::Classic);
+        assert_eq!(Variants::from(Variant::Diagonal), Variant::Diagonal);
+        assert_ne!(both, Variant::Diagonal);
+        assert_eq!(both | Variant::Diagonal, both);
+        let grid = SudokuGrid::default().with_variant(both);
+        assert_eq!(grid.map(|grid| grid.variant()).ok(), Some(both));
+    }
+
+    #[test]
+    fn nonconsecutive_grids_rule_out_adjacent_digits() {
+        let grid = SudokuGrid::from_triples([(4, 4, 5)])
+            .unwrap()
+            .with_variant(Variant::NonConsecutive)
+            .unwrap();
+        let candidates: Vec<u8> = grid.candidates(4, 5).collect();
+        assert_eq!(candidates, [1, 2, 3, 7, 8, 9]);
+        assert!(grid.candidates(5, 5).any(|value| value == 4));
+        assert!(!grid.is_legal(3, 4, 6));
+        assert!(!grid.clone().set(4, 3, 4));
+        let mut valid = grid.clone();
+        assert!(valid.set(4, 3, 3));
+        assert!(valid.is_valid());
+
+        // Consecutive neighbors are conflicts
+        let text = format!("{}12{}", ".".repeat(40), ".".repeat(39));
+        let adjacent = SudokuGrid {
+            variant: Variant::NonConsecutive.into(),
+            ..unchecked(&text)
+        };
+        assert_eq!(adjacent.find_conflicts(), [(4, 4, 4, 5)]);
+        assert!(!adjacent.is_valid());
+        let classic = SudokuGrid::from_str_compact(&text).unwrap();
+        assert!(matches!(
+            classic.with_variant(Variant::NonConsecutive),
+            Err(SudokuError::InvalidPuzzle { .. })
+        ));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod dlx;
+pub mod generator;
+pub mod input;
+#[cfg(feature = "image")]
+pub mod render;
+pub mod samurai;
+pub mod sized;
+pub mod solver;
+pub mod strategy;
+pub mod transform;
@@ -1,30 +1,211 @@
-use std::path::PathBuf;
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use clap::Parser;
-use solver::Solver;
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use sudoku_rs::{
+    dlx,
+    generator::Generator,
+    input::{cell_name, PuzzleSource, SudokuError, SudokuGrid, Variant, Variants},
+    solver::{self, CellOrder, Difficulty, Solver, SolverConfig, Technique},
+};
 
-use crate::input::SudokuGrid;
-
-mod input;
-mod solver;
+mod repl;
 
 /// Command line utility to solve sudoku puzzles
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// Input file containing the sudoku puzzle
-    input: PathBuf,
+    /// Input file containing the sudoku puzzle, read from stdin if not given or `-`
+    input: Option<PathBuf>,
+
+    /// Give up solving after this many milliseconds
+    #[arg(long, value_name = "MS")]
+    timeout: Option<u64>,
+
+    /// Solve one puzzle per line in the 81-character format, printing one solution per line
+    #[arg(long, conflicts_with = "timeout")]
+    batch: bool,
+
+    /// Check every puzzle of a file with one 81-character puzzle per line,
+    /// reporting the lines that are invalid
+    #[arg(long, conflicts_with_all = ["timeout", "batch"])]
+    validate_all: bool,
+
+    /// Print the steps of a human-style solve as a JSON array instead of the solution
+    #[arg(long, conflicts_with_all = ["timeout", "batch", "validate_all"])]
+    explain_json: bool,
+
+    /// Solve the puzzle in this file with each solver backend and print a table of the work
+    /// each one took, reading stdin if the file is `-`
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["input", "timeout", "batch", "validate_all", "explain_json"])]
+    compare: Option<PathBuf>,
+
+    /// Solve only the puzzle at this 0-based index of a file with one 81-character
+    /// puzzle per line
+    #[arg(long, value_name = "K", conflicts_with_all = ["batch", "validate_all", "explain_json", "compare"])]
+    index: Option<usize>,
+
+    /// Write the grid to this directory as a PNG image after every placement while solving,
+    /// as frames for an animation
+    #[cfg(feature = "image")]
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["batch", "validate_all", "explain_json", "compare"])]
+    frames: Option<PathBuf>,
+
+    /// Solve X-Sudoku, where both main diagonals must also contain every digit once
+    #[arg(long)]
+    diagonal: bool,
+
+    /// Solve disjoint groups Sudoku, where the cells at the same position within each
+    /// square must also contain every digit once
+    #[arg(long)]
+    disjoint_groups: bool,
+
+    /// Solve nonconsecutive Sudoku, where orthogonally adjacent cells must not hold
+    /// consecutive digits
+    #[arg(long)]
+    nonconsecutive: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the cells of the second puzzle that differ from the first one
+    Diff {
+        /// File containing the first puzzle
+        a: PathBuf,
+        /// File containing the second puzzle
+        b: PathBuf,
+    },
+    /// Generate distinct puzzles with a unique solution, printing one per line
+    /// in the 81-character format
+    Generate {
+        /// Number of puzzles to generate
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        /// Number of clues to aim for, puzzles may have more if no more clues can be removed
+        #[arg(long, default_value_t = 30)]
+        clues: usize,
+        /// Seed of the generator, the same seed always gives the same puzzles.
+        /// A seed is picked from the current time if not given.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Print the puzzles from the easiest to the hardest, as rated by `Solver::rate`
+        #[arg(long)]
+        sort: bool,
+        /// Also print how many puzzles fell into each difficulty tier to stderr
+        #[arg(long)]
+        histogram: bool,
+    },
+    /// Start an interactive session for editing and solving a puzzle
+    Repl {
+        /// File containing the puzzle to start with, an empty grid is used if not given
+        input: Option<PathBuf>,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    let grid = SudokuGrid::from_file(&cli.input)?;
+    match cli.command {
+        Some(Command::Diff { a, b }) => diff(a, b),
+        Some(Command::Generate {
+            count,
+            clues,
+            seed,
+            sort,
+            histogram,
+        }) => {
+            generate(count, clues, seed, sort, histogram);
+            Ok(())
+        }
+        Some(Command::Repl { input }) => {
+            let grid = match input {
+                Some(input) => PuzzleSource::File(input).load()?,
+                None => SudokuGrid::default(),
+            };
+            repl::run(grid)
+        }
+        None => {
+            let variant: Variants = [
+                (cli.diagonal, Variant::Diagonal),
+                (cli.disjoint_groups, Variant::DisjointGroups),
+                (cli.nonconsecutive, Variant::NonConsecutive),
+            ]
+            .into_iter()
+            .filter_map(|(enabled, variant)| enabled.then_some(variant))
+            .collect();
+            if cli.batch {
+                batch(cli.input, variant)
+            } else if cli.validate_all {
+                validate_all(cli.input, variant)
+            } else if cli.explain_json {
+                explain_json(cli.input, variant)
+            } else if let Some(file) = cli.compare {
+                compare(file, variant)
+            } else {
+                #[cfg(feature = "image")]
+                if let Some(dir) = cli.frames {
+                    return frames(cli.input, &dir, variant);
+                }
+                solve(cli.input, cli.index, cli.timeout, variant)
+            }
+        }
+    }
+}
+
+fn source(input: Option<PathBuf>) -> PuzzleSource {
+    match input {
+        Some(input) if input.as_os_str() != "-" => PuzzleSource::File(input),
+        _ => PuzzleSource::Stdin,
+    }
+}
+
+fn solve(
+    input: Option<PathBuf>,
+    index: Option<usize>,
+    timeout: Option<u64>,
+    variant: Variants,
+) -> anyhow::Result<()> {
+    let loaded = match index {
+        Some(index) => SudokuGrid::nth_from_reader(open_lines(input)?, index, variant),
+        None => source(input)
+            .load()
+            .and_then(|grid| grid.with_variant(variant)),
+    };
+    let grid = match loaded {
+        Err(SudokuError::InvalidPuzzle { conflicts }) => {
+            println!("Puzzle is invalid:");
+            for conflict in conflicts {
+                println!("  {}", conflict);
+            }
+            std::process::exit(1);
+        }
+        grid => grid?,
+    };
 
     println!("Input:");
     println!("{}", grid);
 
-    let solver = Solver::new(grid);
-    let solution = solver.solve();
+    let solver = Solver::from_grid(grid);
+    let solution = match timeout {
+        Some(timeout) => {
+            let deadline = Instant::now() + Duration::from_millis(timeout);
+            match solver.solve_until(deadline) {
+                Ok(solution) => solution,
+                Err(_) => {
+                    println!("Timed out");
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => solver.solve(),
+    };
 
     if let Some(solution) = solution {
         println!("Solution:");
@@ -35,3 +216,270 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Solve a puzzle step by step, writing the grid to a directory as `frame-NNNN.png`
+/// after every placement, then print the solution like `solve`.
+#[cfg(feature = "image")]
+fn frames(input: Option<PathBuf>, dir: &std::path::Path, variant: Variants) -> anyhow::Result<()> {
+    use sudoku_rs::solver::{SolveStep, StepSolver};
+
+    let mut grid = source(input).load()?.with_variant(variant)?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let mut count = 0;
+    let mut solved = false;
+    for step in StepSolver::new(grid.clone()) {
+        match step {
+            SolveStep::Place { row, col, value } => {
+                if !grid.set(row, col, value) {
+                    anyhow::bail!(
+                        "The solver placed {} at {}, which conflicts with the grid",
+                        value,
+                        cell_name(row, col)
+                    );
+                }
+                count += 1;
+                grid.render_png(dir.join(format!("frame-{:04}.png", count)))?;
+            }
+            SolveStep::Remove { row, col } => grid.unset(row, col),
+            SolveStep::Done => solved = true,
+            SolveStep::Failed => {}
+        }
+    }
+    eprintln!("Wrote {} frames to {:?}", count, dir);
+    if solved {
+        println!("Solution:");
+        println!("{}", grid);
+    } else {
+        println!("No solution found");
+    }
+    Ok(())
+}
+
+/// Open the input for reading line by line.
+fn open_lines(input: Option<PathBuf>) -> anyhow::Result<Box<dyn BufRead>> {
+    Ok(match source(input) {
+        PuzzleSource::File(path) => {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("Failed to read file {:?}", path))?;
+            Box::new(BufReader::new(file))
+        }
+        _ => Box::new(std::io::stdin().lock()),
+    })
+}
+
+fn batch(input: Option<PathBuf>, variant: Variants) -> anyhow::Result<()> {
+    let mut puzzles: Vec<_> =
+        SudokuGrid::stream_variant_from_reader(open_lines(input)?, variant).collect();
+
+    // Move the valid grids out to the solver, leaving the parse errors in place
+    let grids = puzzles
+        .iter_mut()
+        .filter_map(|puzzle| puzzle.as_mut().ok().map(std::mem::take));
+    let mut solutions = solver::solve_many(grids).into_iter();
+    for puzzle in puzzles {
+        match puzzle {
+            Ok(_) => match solutions.next().flatten() {
+                Some(solution) => println!("{}", solution.to_compact_string()),
+                None => println!("UNSOLVABLE"),
+            },
+            Err(err) => {
+                eprintln!("{:#}", anyhow::Error::from(err));
+                println!("INVALID");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_all(input: Option<PathBuf>, variant: Variants) -> anyhow::Result<()> {
+    let mut total = 0;
+    let mut invalid = 0;
+    for result in SudokuGrid::stream_variant_from_reader(open_lines(input)?, variant) {
+        total += 1;
+        match result {
+            Ok(_) => {}
+            Err(SudokuError::InvalidLine { line, source }) => {
+                invalid += 1;
+                match *source {
+                    SudokuError::InvalidPuzzle { conflicts } => {
+                        println!("Line {}: Puzzle is invalid:", line);
+                        for conflict in conflicts {
+                            println!("  {}", conflict);
+                        }
+                    }
+                    err => println!("Line {}: {}", line, err),
+                }
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    println!("{} of {} puzzles are invalid", invalid, total);
+
+    if invalid > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print the steps of `Solver::solve_with_techniques` as JSON objects with the fields
+/// `type` ("place", "guess", or "eliminate"), `cell`, `value`, `technique`, and `eliminations`.
+/// A step that only eliminates candidates has no cell or value, and the candidates it rules
+/// out are listed as objects with a `cell` and a `value`.
+fn explain_json(input: Option<PathBuf>, variant: Variants) -> anyhow::Result<()> {
+    let grid = source(input).load()?.with_variant(variant)?;
+    let Some((_, steps)) = Solver::from_grid(grid).solve_with_techniques() else {
+        eprintln!("No solution found");
+        std::process::exit(1);
+    };
+
+    let mut objects = Vec::new();
+    for step in steps {
+        let technique = format!("{:?}", step.technique);
+        if !step.eliminations.is_empty() {
+            let eliminations: Vec<String> = step
+                .eliminations
+                .iter()
+                .map(|&(row, col, value)| {
+                    format!(r#"{{"cell":"{}","value":{}}}"#, cell_name(row, col), value)
+                })
+                .collect();
+            objects.push(format!(
+                r#"{{"type":"eliminate","cell":null,"value":null,"technique":"{}","eliminations":[{}]}}"#,
+                technique,
+                eliminations.join(",")
+            ));
+        }
+        let kind = if step.technique == Technique::Guess {
+            "guess"
+        } else {
+            "place"
+        };
+        for (row, col, value) in step.placements {
+            objects.push(format!(
+                r#"{{"type":"{}","cell":"{}","value":{},"technique":"{}","eliminations":[]}}"#,
+                kind,
+                cell_name(row, col),
+                value,
+                technique
+            ));
+        }
+    }
+    println!("[");
+    println!("  {}", objects.join(",\n  "));
+    println!("]");
+
+    Ok(())
+}
+
+/// The backends compared by `--compare`: a name and a solver configuration, or None for
+/// dancing links
+const BACKENDS: [(&str, Option<SolverConfig>); 6] = [
+    ("backtracking", Some(config(CellOrder::RowMajor, false))),
+    ("mrv", Some(config(CellOrder::Mrv, false))),
+    ("mrv+propagation", Some(config(CellOrder::Mrv, true))),
+    (
+        "mrv+random",
+        Some(config(CellOrder::MrvRandomTiebreak, true)),
+    ),
+    (
+        "mrv+degree",
+        Some(config(CellOrder::MostConstrainingVariable, true)),
+    ),
+    ("dlx", None),
+];
+
+const fn config(cell_order: CellOrder, propagate: bool) -> SolverConfig {
+    SolverConfig {
+        cell_order,
+        propagate,
+        seed: 0,
+    }
+}
+
+fn compare(file: PathBuf, variant: Variants) -> anyhow::Result<()> {
+    let grid = source(Some(file)).load()?.with_variant(variant)?;
+
+    println!(
+        "{:<16} {:<8} {:>10} {:>10} {:>10}",
+        "backend", "result", "guesses", "backtracks", "time"
+    );
+    for (name, config) in BACKENDS {
+        let start = Instant::now();
+        let (solution, stats) = match config {
+            Some(config) => Solver::from_grid(grid.clone())
+                .with_config(config)
+                .solve_with_stats(),
+            None => dlx::solve_with_stats(&grid),
+        };
+        let elapsed = start.elapsed();
+        println!(
+            "{:<16} {:<8} {:>10} {:>10} {:>10}",
+            name,
+            if solution.is_some() { "solved" } else { "none" },
+            stats.guesses,
+            stats.backtracks,
+            format!("{:.3}ms", elapsed.as_secs_f64() * 1000.0)
+        );
+    }
+
+    Ok(())
+}
+
+fn generate(count: usize, clues: usize, seed: Option<u64>, sort: bool, histogram: bool) {
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    let mut generator = Generator::new(seed);
+    let puzzles = generator.puzzles(clues).take(count);
+    if !sort && !histogram {
+        for puzzle in puzzles {
+            println!("{}", puzzle.to_compact_string());
+        }
+        return;
+    }
+
+    // Generated puzzles always have a solution, so they always have a rating
+    let mut rated: Vec<_> = puzzles
+        .map(|puzzle| (Solver::from_grid(puzzle.clone()).rate().unwrap(), puzzle))
+        .collect();
+    if sort {
+        rated.sort_by_key(|(rating, _)| *rating);
+    }
+    for (_, puzzle) in rated.iter() {
+        println!("{}", puzzle.to_compact_string());
+    }
+    if histogram {
+        for difficulty in Difficulty::ALL {
+            let tally = rated
+                .iter()
+                .filter(|(rating, _)| rating.difficulty() == difficulty)
+                .count();
+            eprintln!("{:<8} {:>5}", format!("{:?}", difficulty), tally);
+        }
+    }
+}
+
+fn diff(a: PathBuf, b: PathBuf) -> anyhow::Result<()> {
+    let a = PuzzleSource::File(a).load()?;
+    let b = PuzzleSource::File(b).load()?;
+
+    let cells = a.diff(&b);
+    for i in 0..9 {
+        for j in 0..9 {
+            if !cells.contains(&(i, j)) {
+                print!(".");
+            } else if b.at(i, j) == 0 {
+                print!("_");
+            } else {
+                print!("{}", b.at(i, j));
+            }
+        }
+        println!();
+    }
+    println!("{} differences", cells.len());
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Rendering grids to PNG images, behind the `image` feature.
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use crate::input::{SudokuError, SudokuGrid};
+
+/// The width and height of a cell in pixels
+const CELL: usize = 40;
+/// The width and height of the image in pixels, with room for the outer border
+const SIZE: usize = 9 * CELL + 3;
+/// How many pixels each dot of the digit font takes in each direction
+const SCALE: usize = 5;
+
+/// A 3x5 font for the digits 1 to 9, one row per byte with the leftmost dot in bit 2
+const DIGITS: [[u8; 5]; 9] = [
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+impl SudokuGrid {
+    /// Render the grid as a grayscale PNG image, with thick lines around the squares
+    /// and the digits of the filled cells.
+    pub fn render_png(&self, path: impl AsRef<Path>) -> Result<(), SudokuError> {
+        let path = path.as_ref();
+        let image_error = |source| SudokuError::Image {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = File::create(path).map_err(image_error)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), SIZE as u32, SIZE as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let encoding_error = |err| image_error(std::io::Error::other(err));
+        let mut writer = encoder.write_header().map_err(encoding_error)?;
+        writer
+            .write_image_data(&self.pixels())
+            .map_err(encoding_error)?;
+        writer.finish().map_err(encoding_error)
+    }
+
+    /// The pixels of the rendered grid in row-major order, 0 for black and 255 for white.
+    fn pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![255; SIZE * SIZE];
+        // Lines between the cells, the ones around the squares 3 pixels wide
+        for k in 0..=9 {
+            let width = if k % 3 == 0 { 3 } else { 1 };
+            for offset in 0..width {
+                let line = k * CELL + offset + (3 - width) / 2;
+                for along in 0..SIZE {
+                    pixels[line * SIZE + along] = 0;
+                    pixels[along * SIZE + line] = 0;
+                }
+            }
+        }
+        // Digits centered in their cells
+        let margin_x = (CELL - 3 * SCALE) / 2 + 1;
+        let margin_y = (CELL - 5 * SCALE) / 2 + 1;
+        for (row, col) in (0..81).map(|k| (k / 9, k % 9)) {
+            let value = self.at(row, col);
+            if value == 0 {
+                continue;
+            }
+            for (dy, dots) in DIGITS[value as usize - 1].iter().enumerate() {
+                for dx in (0..3).filter(|dx| dots & (0b100 >> dx) != 0) {
+                    let top = row * CELL + margin_y + dy * SCALE;
+                    let left = col * CELL + margin_x + dx * SCALE;
+                    for y in top..top + SCALE {
+                        pixels[y * SIZE + left..y * SIZE + left + SCALE].fill(0);
+                    }
+                }
+            }
+        }
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_png_writes_an_image_of_the_grid() {
+        let path = std::env::temp_dir().join(format!("sudoku-render-{}.png", std::process::id()));
+        let grid = SudokuGrid::from_triples([(0, 0, 5), (4, 4, 8)]).unwrap();
+        grid.render_png(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().width as usize, SIZE);
+        assert_eq!(reader.info().height as usize, SIZE);
+
+        // Filled cells have dark pixels inside them and empty ones do not
+        let pixels = grid.pixels();
+        let dark = |row: usize, col: usize| {
+            (3..CELL)
+                .any(|y| (3..CELL).any(|x| pixels[(row * CELL + y) * SIZE + col * CELL + x] == 0))
+        };
+        assert!(dark(0, 0) && dark(4, 4));
+        assert!(!dark(0, 1) && !dark(8, 8));
+    }
+
+    #[test]
+    fn render_png_reports_unwritable_paths() {
+        let path = std::env::temp_dir()
+            .join("sudoku-missing-dir")
+            .join("grid.png");
+        let err = SudokuGrid::default().render_png(&path).unwrap_err();
+        assert!(matches!(err, SudokuError::Image { .. }));
+    }
+}
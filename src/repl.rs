@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use sudoku_rs::{
+    input::{cell_name, parse_cell_name, SudokuGrid},
+    solver::Solver,
+};
+
+const HELP: &str = "\
+Commands:
+  show             Print the grid
+  set R#C# <digit> Set the value of a cell
+  unset R#C#       Clear a cell
+  candidates R#C#  List the digits that can be placed in a cell
+  hint             Reveal the value of one empty cell
+  solve            Replace the grid with its solution
+  help             Print this message
+  quit             Exit";
+
+/// Run an interactive loop reading commands from stdin and applying them to the grid.
+pub fn run(mut grid: SudokuGrid) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout();
+    println!("{}", grid);
+    loop {
+        print!("> ");
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            break;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["show"] => println!("{}", grid),
+            ["set", cell, value] => match (parse_cell_name(cell), value.parse::<u8>()) {
+                (Some((row, col)), Ok(value @ 1..=9)) => {
+                    if grid.at(row, col) != 0 {
+                        println!("{} is already filled", cell_name(row, col));
+                    } else if !grid.set(row, col, value) {
+                        println!("{} cannot be placed at {}", value, cell_name(row, col));
+                    }
+                }
+                _ => println!("Usage: set R#C# <digit>"),
+            },
+            ["unset", cell] => match parse_cell_name(cell) {
+                Some((row, col)) => {
+                    if grid.at(row, col) != 0 {
+                        grid.unset(row, col);
+                    }
+                }
+                None => println!("Usage: unset R#C#"),
+            },
+            ["candidates", cell] => match parse_cell_name(cell) {
+                Some((row, col)) => {
+                    let digits: Vec<String> = grid
+                        .candidates(row, col)
+                        .map(|value| value.to_string())
+                        .collect();
+                    println!("{}: {}", cell_name(row, col), digits.join(" "));
+                }
+                None => println!("Usage: candidates R#C#"),
+            },
+            ["hint"] => match hint(&grid) {
+                Some((row, col, value)) => println!("{} = {}", cell_name(row, col), value),
+                None => println!("No hint available"),
+            },
+            ["solve"] => match Solver::from_grid(grid.clone()).solve() {
+                Some(solution) => {
+                    grid = solution;
+                    println!("{}", grid);
+                }
+                None => println!("No solution found"),
+            },
+            ["help"] => println!("{}", HELP),
+            ["quit"] | ["exit"] => break,
+            _ => println!("Unknown command, type 'help' for a list of commands"),
+        }
+    }
+    Ok(())
+}
+
+/// Find the value of an empty cell, preferring a cell with a single candidate.
+/// Returns None if the grid is full or has no solution.
+fn hint(grid: &SudokuGrid) -> Option<(usize, usize, u8)> {
+    let solution = Solver::from_grid(grid.clone()).solve()?;
+    let empty_cells = (0..81)
+        .map(|k| (k / 9, k % 9))
+        .filter(|&(row, col)| grid.at(row, col) == 0);
+    let (row, col) = empty_cells
+        .clone()
+        .find(|&(row, col)| grid.candidates_mask(row, col).count_ones() == 1)
+        .or_else(|| empty_cells.clone().next())?;
+    Some((row, col, solution.at(row, col)))
+}
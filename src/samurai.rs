@@ -0,0 +1,424 @@
+//! Samurai Sudoku: five 9x9 grids overlapping in a 21x21 layout.
+//!
+//! The four corner grids each share one square with the center grid: the top left grid
+//! shares its bottom right square, the top right grid its bottom left square, and so on.
+//! A cell in a shared square belongs to two grids and must follow the rules of both.
+
+use std::fmt;
+
+use crate::input::{Conflict, ConflictPair, SudokuError, SudokuGrid, Variant};
+
+/// The width and height of the layout in cells
+pub const SIZE: usize = 21;
+
+/// The positions of the top left cells of the grids in the layout: the top left,
+/// top right, center, bottom left, and bottom right grids, in that order
+pub const OFFSETS: [(usize, usize); 5] = [(0, 0), (0, 12), (6, 6), (12, 0), (12, 12)];
+
+/// The grids a cell of the layout belongs to, with the cell's position within each grid.
+fn grids_of(row: usize, col: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+    OFFSETS
+        .iter()
+        .enumerate()
+        .filter(move |&(_, &(top, left))| {
+            (top..top + 9).contains(&row) && (left..left + 9).contains(&col)
+        })
+        .map(move |(grid, &(top, left))| (grid, row - top, col - left))
+}
+
+/// The cells of the layout that belong to at least one grid, in row-major order.
+fn cells() -> impl Iterator<Item = (usize, usize)> {
+    (0..SIZE * SIZE)
+        .map(|k| (k / SIZE, k % SIZE))
+        .filter(|&(row, col)| grids_of(row, col).next().is_some())
+}
+
+/// A Samurai puzzle, as the five grids of its layout.
+/// The cells of the shared squares always hold the same value in both of their grids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Samurai {
+    grids: [SudokuGrid; 5],
+}
+
+impl Samurai {
+    /// Parse a puzzle in the 21x21 layout: 21 lines with one character per cell,
+    /// a digit for a clue, and '.', '0', or '_' for an empty cell.
+    /// The positions outside of the grids hold spaces, and trailing spaces may be left out.
+    /// Lines starting with '#' are ignored.
+    ///
+    /// Returns `SudokuError::Layout` if the input does not have 21 lines of at most
+    /// 21 cells or has a cell outside of the grids, and `SudokuError::InvalidPuzzle`
+    /// listing every pair of clues that clash in one of the grids, with the rows and
+    /// columns of the layout.
+    pub fn parse(input: &str) -> Result<Self, SudokuError> {
+        let lines: Vec<&str> = input
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        if lines.len() != SIZE {
+            return Err(SudokuError::Layout(format!(
+                "expected {} lines, found {}",
+                SIZE,
+                lines.len()
+            )));
+        }
+        let mut cells = [[[0; 9]; 9]; 5];
+        for (row, line) in lines.iter().enumerate() {
+            for (col, ch) in line.trim_end().chars().enumerate() {
+                let value = match ch {
+                    ' ' => continue,
+                    '.' | '0' | '_' => 0,
+                    '1'..='9' => ch as u8 - b'0',
+                    _ => {
+                        return Err(SudokuError::InvalidChar {
+                            ch,
+                            line: row + 1,
+                            col: col + 1,
+                        })
+                    }
+                };
+                if col >= SIZE || grids_of(row, col).next().is_none() {
+                    return Err(SudokuError::Layout(format!(
+                        "cell at line {}, column {} is outside of the grids",
+                        row + 1,
+                        col + 1
+                    )));
+                }
+                for (grid, i, j) in grids_of(row, col) {
+                    cells[grid][i][j] = value;
+                }
+            }
+        }
+        let mut grids = Vec::with_capacity(5);
+        let mut conflicts = Vec::new();
+        for (cells, (top, left)) in cells.into_iter().zip(OFFSETS) {
+            match SudokuGrid::from_cells(cells, Variant::Classic) {
+                Ok(grid) => grids.push(grid),
+                Err(SudokuError::InvalidPuzzle { conflicts: pairs }) => {
+                    let shift = |given: Conflict| Conflict {
+                        row: given.row + top,
+                        col: given.col + left,
+                        ..given
+                    };
+                    for pair in pairs {
+                        let pair = ConflictPair {
+                            first: shift(pair.first),
+                            second: shift(pair.second),
+                        };
+                        // Both grids of a shared square report its conflicts
+                        if !conflicts.contains(&pair) {
+                            conflicts.push(pair);
+                        }
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(SudokuError::InvalidPuzzle { conflicts });
+        }
+        Ok(Self {
+            grids: grids.try_into().expect("there are five grids"),
+        })
+    }
+
+    /// Borrow the five grids, in the order of `OFFSETS`.
+    pub fn grids(&self) -> &[SudokuGrid; 5] {
+        &self.grids
+    }
+
+    /// Get the value of a cell of the layout, with 0 for an empty cell.
+    /// Returns None for a position outside of the grids.
+    pub fn at(&self, row: usize, col: usize) -> Option<u8> {
+        grids_of(row, col)
+            .next()
+            .map(|(grid, i, j)| self.grids[grid].at(i, j))
+    }
+
+    /// Check if every cell of the layout is filled.
+    pub fn is_complete(&self) -> bool {
+        self.grids
+            .iter()
+            .all(|grid| grid.empty_cells().next().is_none())
+    }
+
+    /// Find a solution of the puzzle, filling every grid while keeping the shared
+    /// squares equal. Returns None if there is no solution.
+    ///
+    /// The search fills the empty cell of the layout with the fewest candidates first,
+    /// where the candidates of a shared cell are the digits allowed by both of its grids.
+    pub fn solve(&self) -> Option<Samurai> {
+        let mut samurai = self.clone();
+        samurai.search().then_some(samurai)
+    }
+
+    /// The digits that can be placed in a cell of the layout without breaking
+    /// the rules of any of its grids, as a bitmask.
+    fn candidates_mask(&self, row: usize, col: usize) -> u16 {
+        grids_of(row, col).fold(0x1ff, |mask, (grid, i, j)| {
+            mask & self.grids[grid].candidates_mask(i, j)
+        })
+    }
+
+    /// Backtrack until every cell is filled. Returns false if no solution was found,
+    /// leaving the grids as they were.
+    fn search(&mut self) -> bool {
+        let Some((row, col, mask)) = cells()
+            .filter(|&(row, col)| self.at(row, col) == Some(0))
+            .map(|(row, col)| (row, col, self.candidates_mask(row, col)))
+            .min_by_key(|&(_, _, mask)| mask.count_ones())
+        else {
+            return true;
+        };
+        for value in (1..=9u8).filter(|value| mask & (1 << (value - 1)) != 0) {
+            for (grid, i, j) in grids_of(row, col) {
+                assert!(self.grids[grid].set(i, j, value));
+            }
+            if self.search() {
+                return true;
+            }
+            for (grid, i, j) in grids_of(row, col) {
+                self.grids[grid].unset(i, j);
+            }
+        }
+        false
+    }
+}
+
+impl std::str::FromStr for Samurai {
+    type Err = SudokuError;
+
+    fn from_str(s: &str) -> Result<Self, SudokuError> {
+        Self::parse(s)
+    }
+}
+
+/// Display the puzzle in the 21x21 layout accepted by `Samurai::parse`,
+/// with '.' for empty cells.
+impl fmt::Display for Samurai {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in 0..SIZE {
+            let line: String = (0..SIZE)
+                .map(|col| match self.at(row, col) {
+                    None => ' ',
+                    Some(0) => '.',
+                    Some(value) => (b'0' + value) as char,
+                })
+                .collect();
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A layout with every cell empty
+    fn empty() -> String {
+        (0..SIZE)
+            .map(|row| {
+                let line: String = (0..SIZE)
+                    .map(|col| match grids_of(row, col).next() {
+                        Some(_) => '.',
+                        None => ' ',
+                    })
+                    .collect();
+                format!("{}\n", line.trim_end())
+            })
+            .collect()
+    }
+
+    /// Join the lines of a layout being edited
+    fn layout_string(layout: &[Vec<u8>]) -> String {
+        layout
+            .iter()
+            .map(|line| format!("{}\n", String::from_utf8(line.clone()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn samurai_layout_round_trips() {
+        let layout = empty();
+        assert!(layout.starts_with(".........   .........\n"));
+        assert_eq!(layout.lines().nth(9), Some("      ........."));
+        let samurai = Samurai::parse(&layout).unwrap();
+        assert_eq!(samurai.to_string(), layout);
+        assert_eq!(samurai.at(0, 10), None);
+        assert_eq!(samurai.at(10, 10), Some(0));
+    }
+
+    #[test]
+    fn shared_squares_link_the_grids() {
+        let mut layout: Vec<Vec<u8>> = empty().lines().map(|line| line.into()).collect();
+        // R7C7 of the layout is R7C7 of the top left grid and R1C1 of the center grid
+        layout[6][6] = b'4';
+        let samurai = Samurai::parse(&layout_string(&layout)).unwrap();
+        assert_eq!(samurai.grids()[0].at(6, 6), 4);
+        assert_eq!(samurai.grids()[2].at(0, 0), 4);
+        assert_eq!(samurai.grids()[1].at(6, 6), 0);
+
+        // A 4 on the first row of the center grid clashes with it in the center grid only,
+        // and one in the same shared square is reported once for both of its grids
+        layout[6][12] = b'4';
+        layout[8][8] = b'4';
+        let Err(SudokuError::InvalidPuzzle { conflicts }) = Samurai::parse(&layout_string(&layout))
+        else {
+            panic!("the clashing clues are not reported");
+        };
+        let cells: Vec<_> = conflicts
+            .iter()
+            .map(|pair| {
+                (
+                    (pair.first.row, pair.first.col),
+                    (pair.second.row, pair.second.col),
+                )
+            })
+            .collect();
+        assert_eq!(cells, [((6, 6), (8, 8)), ((6, 6), (6, 12))]);
+        assert!(conflicts.iter().all(|pair| pair.first.value == 4));
+    }
+
+    #[test]
+    fn invalid_layouts_are_rejected() {
+        let layout = empty();
+        let outside = layout.replacen(".........   .........", "..........  .........", 1);
+        assert!(matches!(
+            Samurai::parse(&outside),
+            Err(SudokuError::Layout(_))
+        ));
+        let short: String = layout
+            .lines()
+            .skip(1)
+            .map(|line| format!("{line}\n"))
+            .collect();
+        assert!(matches!(
+            Samurai::parse(&short),
+            Err(SudokuError::Layout(_))
+        ));
+        let invalid = layout.replacen('.', "x", 1);
+        assert!(matches!(
+            Samurai::parse(&invalid),
+            Err(SudokuError::InvalidChar { ch: 'x', .. })
+        ));
+    }
+
+    #[test]
+    fn solve_fills_all_five_grids() {
+        let solution = Samurai::parse(&empty()).unwrap().solve().unwrap();
+        assert!(solution.is_complete());
+        assert!(solution.grids().iter().all(SudokuGrid::is_valid));
+        // Each corner grid shares a square with the center grid
+        for (grid, (top, left)) in OFFSETS.into_iter().enumerate() {
+            for (row, col) in (0..81).map(|k| (top + k / 9, left + k % 9)) {
+                assert_eq!(
+                    solution.grids()[grid].at(row - top, col - left),
+                    solution.at(row, col).unwrap()
+                );
+            }
+        }
+
+        // Keep every third cell of the solution as a clue and solve again
+        let puzzle: String = solution
+            .to_string()
+            .chars()
+            .enumerate()
+            .map(|(k, ch)| {
+                if ch.is_ascii_digit() && k % 3 != 0 {
+                    '.'
+                } else {
+                    ch
+                }
+            })
+            .collect();
+        let puzzle = Samurai::parse(&puzzle).unwrap();
+        assert!(!puzzle.is_complete());
+        let solved = puzzle.solve().unwrap();
+        for (grid, given) in solved.grids().iter().zip(puzzle.grids()) {
+            assert!(grid.is_solution_of(given));
+        }
+    }
+
+    /// A Samurai built from the puzzle of the Wikipedia article on Sudoku: the center grid
+    /// is that puzzle, and each corner grid swaps its first and last bands and stacks so
+    /// that the shared square matches, then swaps rows, bands, or stacks away from it.
+    /// Each grid keeps a single solution, so the Samurai has one as well.
+    const WIKIPEDIA: &str = "\
+.6...38..   ..5...419
+8.3..14..   28..6....
+.2...67..   .79....8.
+...28..6.   ..38...6.
+419..5...   ..14..8.3
+.8..79...   ..67...2.
+.7....53..7....53..7.
+195...6..195...6..195
+....6..98....6..98...
+      8...6...3
+      4..8.3..1
+      7...2...6
+82.....6....28..6....
+..5419...419..5...419
+7.9.8.....8..79....8.
+....7.53.   ..38...6.
+...1956..   ..14..8.3
+6......98   ..67...2.
+..3.6.8..   ...53..7.
+..18.34..   .6..98...
+..6.2.7..   ...6..195
+";
+
+    /// The solution of `WIKIPEDIA`, with the published solution in the center grid
+    /// and the same swaps of it in the corner grids
+    const WIKIPEDIA_SOLUTION: &str = "\
+761423859   635287419
+853791426   284961537
+924856713   179345286
+537284961   423859761
+419635287   791426853
+286179345   856713924
+678912534678912534678
+195348672195348672195
+342567198342567198342
+      859761423
+      426853791
+      713924856
+824537961537284961537
+365419287419635287419
+719286345286179345286
+192678534   423859761
+438195672   791426853
+657342198   856713924
+243761859   912534678
+971853426   567198342
+586924713   348672195
+";
+
+    #[test]
+    fn samurai_from_a_published_puzzle_is_solved() {
+        let solution = Samurai::parse(WIKIPEDIA_SOLUTION).unwrap();
+        assert!(solution.is_complete());
+        assert!(solution.grids().iter().all(SudokuGrid::is_valid));
+        let puzzle = Samurai::parse(WIKIPEDIA).unwrap();
+        let center: SudokuGrid =
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79"
+                .parse()
+                .unwrap();
+        assert_eq!(puzzle.grids()[2], center);
+        assert_eq!(puzzle.solve(), Some(solution));
+    }
+
+    #[test]
+    fn unsolvable_samurai_gives_none() {
+        let mut layout: Vec<Vec<u8>> = empty().lines().map(|line| line.into()).collect();
+        // R7C7 of the layout sees 1 to 4 in its row and 5 to 8 in its column of the
+        // top left grid, and 9 in its row of the center grid
+        layout[6][..4].copy_from_slice(b"1234");
+        for (k, digit) in b"5678".iter().enumerate() {
+            layout[k][6] = *digit;
+        }
+        layout[6][9] = b'9';
+        let samurai = Samurai::parse(&layout_string(&layout)).unwrap();
+        assert_eq!(samurai.solve(), None);
+    }
+}
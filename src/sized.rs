@@ -0,0 +1,406 @@
+//! Grids of other sizes than the classic 9x9 one.
+//!
+//! A grid of order `n` has `n * n` rows and columns, split into squares of `n x n` cells,
+//! and holds the digits from 1 to `n * n`. Order 2 gives 4x4 grids and order 4 gives
+//! 16x16 grids, whose digits above 9 are written as letters: `A` for 10 up to `G` for 16.
+//! `SudokuGrid` remains the type for 9x9 grids, with the variants and solvers built for them.
+
+use std::fmt;
+
+use crate::input::{Conflict, SudokuError};
+
+/// The smallest and largest supported orders, up to 25x25 grids so that the digits
+/// of a unit fit in a `u32` bitmask
+pub const ORDERS: std::ops::RangeInclusive<usize> = 2..=5;
+
+/// A grid of any supported order, with the digits used in each row, column, and square
+/// kept as bitmasks like in `SudokuGrid`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizedGrid {
+    order: usize,
+    /// The cells in row-major order, 0 for empty cells
+    cells: Vec<u8>,
+    rows: Vec<u32>,
+    cols: Vec<u32>,
+    squares: Vec<u32>,
+}
+
+/// The digit of a character: '1' to '9', then 'A' (or 'a') for 10 and so on.
+/// Returns None for any other character.
+fn char_digit(ch: char) -> Option<u8> {
+    match ch {
+        '1'..='9' => Some(ch as u8 - b'0'),
+        'A'..='Z' => Some(ch as u8 - b'A' + 10),
+        'a'..='z' => Some(ch as u8 - b'a' + 10),
+        _ => None,
+    }
+}
+
+/// The character of a digit, the reverse of `char_digit`, with '.' for an empty cell.
+fn digit_char(value: u8) -> char {
+    match value {
+        0 => '.',
+        1..=9 => (b'0' + value) as char,
+        value => (b'A' + value - 10) as char,
+    }
+}
+
+impl SizedGrid {
+    /// Create an empty grid of an order.
+    /// Returns `SudokuError::UnsupportedOrder` if the order is not in `ORDERS`.
+    pub fn new(order: usize) -> Result<Self, SudokuError> {
+        if !ORDERS.contains(&order) {
+            return Err(SudokuError::UnsupportedOrder(order));
+        }
+        let side = order * order;
+        Ok(Self {
+            order,
+            cells: vec![0; side * side],
+            rows: vec![0; side],
+            cols: vec![0; side],
+            squares: vec![0; side],
+        })
+    }
+
+    /// Get the order of the grid, the width of its squares.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Get the number of rows and columns of the grid, the square of its order.
+    pub fn side(&self) -> usize {
+        self.order * self.order
+    }
+
+    /// Get the value of a cell, with 0 for an empty cell.
+    pub fn at(&self, row: usize, col: usize) -> u8 {
+        self.cells[row * self.side() + col]
+    }
+
+    /// The index of the square a cell belongs to.
+    fn square_of(&self, row: usize, col: usize) -> usize {
+        (row / self.order) * self.order + col / self.order
+    }
+
+    /// Set the value of an empty cell.
+    /// Returns false if the cell is filled, the value is not a digit of the grid,
+    /// or the digit is already used in the row, column, or square.
+    #[must_use]
+    pub fn set(&mut self, row: usize, col: usize, value: u8) -> bool {
+        if value == 0 || value as usize > self.side() || self.at(row, col) != 0 {
+            return false;
+        }
+        if self.candidates_mask(row, col) & (1 << (value - 1)) == 0 {
+            return false;
+        }
+        let square = self.square_of(row, col);
+        let side = self.side();
+        self.cells[row * side + col] = value;
+        self.rows[row] |= 1 << (value - 1);
+        self.cols[col] |= 1 << (value - 1);
+        self.squares[square] |= 1 << (value - 1);
+        true
+    }
+
+    /// Empty a cell, making its digit available again in its row, column, and square.
+    pub fn unset(&mut self, row: usize, col: usize) {
+        let value = self.at(row, col);
+        if value == 0 {
+            return;
+        }
+        let square = self.square_of(row, col);
+        let side = self.side();
+        self.cells[row * side + col] = 0;
+        self.rows[row] &= !(1 << (value - 1));
+        self.cols[col] &= !(1 << (value - 1));
+        self.squares[square] &= !(1 << (value - 1));
+    }
+
+    /// Get the digits that can be placed in a cell as a bitmask, where bit `d - 1`
+    /// is set if digit `d` is not used in the cell's row, column, or square.
+    /// Returns 0 for a filled cell.
+    pub fn candidates_mask(&self, row: usize, col: usize) -> u32 {
+        if self.at(row, col) != 0 {
+            return 0;
+        }
+        let all = (1u32 << self.side()) - 1;
+        let used = self.rows[row] | self.cols[col] | self.squares[self.square_of(row, col)];
+        all & !used
+    }
+
+    /// Check if every cell is filled. Since `set` never places a conflicting digit,
+    /// a complete grid is a solution.
+    pub fn is_complete(&self) -> bool {
+        self.cells.iter().all(|&value| value != 0)
+    }
+
+    /// Check if this grid is a solution of a puzzle: it is complete and keeps every
+    /// clue of the puzzle.
+    pub fn is_solution_of(&self, puzzle: &SizedGrid) -> bool {
+        self.order == puzzle.order
+            && self.is_complete()
+            && puzzle
+                .cells
+                .iter()
+                .zip(&self.cells)
+                .all(|(&given, &value)| given == 0 || given == value)
+    }
+
+    /// Parse a grid, working out its order from the number of cells.
+    ///
+    /// The grid is given either as one line per row, or with all the cells on a single
+    /// line in row-major order. Digits above 9 are letters, and empty cells are
+    /// '.', '0', or '_'. Whitespace and the '|', '-', and '+' characters of box borders
+    /// are ignored, as are blank lines, border lines, and lines starting with '#'.
+    ///
+    /// Returns `SudokuError::WrongCellCount` if the number of cells is not the number of
+    /// cells of a supported order, `SudokuError::InvalidChar` for characters that are not
+    /// a digit of the grid, including digits and letters above its side, and the first
+    /// conflict for conflicting digits.
+    pub fn parse(input: &str) -> Result<Self, SudokuError> {
+        // The value of each cell, with the character and its position in the input
+        let mut cells = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            if line.starts_with('#') {
+                continue;
+            }
+            for (j, ch) in line.chars().enumerate() {
+                let invalid = SudokuError::InvalidChar {
+                    ch,
+                    line: i + 1,
+                    col: j + 1,
+                };
+                let value = match ch {
+                    '.' | '0' | '_' => 0,
+                    ch if ch.is_whitespace() || "|-+".contains(ch) => continue,
+                    ch => char_digit(ch).ok_or(invalid)?,
+                };
+                cells.push((value, ch, i + 1, j + 1));
+            }
+        }
+        let order = ORDERS
+            .clone()
+            .find(|order| order.pow(4) == cells.len())
+            .ok_or(SudokuError::WrongCellCount(cells.len()))?;
+        let mut grid = Self::new(order)?;
+        let side = grid.side();
+        for (k, &(value, ch, line, col)) in cells.iter().enumerate() {
+            if value as usize > side {
+                return Err(SudokuError::InvalidChar { ch, line, col });
+            }
+            let (row, col) = (k / side, k % side);
+            if value != 0 && !grid.set(row, col, value) {
+                return Err(Conflict { row, col, value }.into());
+            }
+        }
+        Ok(grid)
+    }
+
+    /// Render the grid with all the cells on a single line in row-major order,
+    /// with '.' for empty cells, as accepted by `parse`.
+    pub fn to_compact_string(&self) -> String {
+        self.cells.iter().map(|&value| digit_char(value)).collect()
+    }
+
+    /// Find a solution by backtracking, filling the empty cell with the fewest
+    /// candidates first. Returns None if there is no solution.
+    pub fn solve(&self) -> Option<SizedGrid> {
+        let mut grid = self.clone();
+        grid.search().then_some(grid)
+    }
+
+    /// Backtrack until every cell is filled. Returns false if no solution was found,
+    /// leaving the grid as it was.
+    fn search(&mut self) -> bool {
+        let side = self.side();
+        let Some((row, col, mask)) = (0..side * side)
+            .map(|k| (k / side, k % side))
+            .filter(|&(row, col)| self.at(row, col) == 0)
+            .map(|(row, col)| (row, col, self.candidates_mask(row, col)))
+            .min_by_key(|&(_, _, mask)| mask.count_ones())
+        else {
+            return true;
+        };
+        for value in (1..=side as u8).filter(|value| mask & (1 << (value - 1)) != 0) {
+            assert!(self.set(row, col, value));
+            if self.search() {
+                return true;
+            }
+            self.unset(row, col);
+        }
+        false
+    }
+}
+
+/// The grid with a border around every square, in the layout of `SudokuGrid`'s
+/// `Display`, with '_' for empty cells.
+impl fmt::Display for SizedGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order = self.order;
+        let dashes = "-".repeat(2 * order + 1);
+        let border = format!("+{}", format!("{}+", dashes).repeat(order));
+        writeln!(f, "{}", border)?;
+        for row in 0..self.side() {
+            write!(f, "|")?;
+            for col in 0..self.side() {
+                let ch = match self.at(row, col) {
+                    0 => '_',
+                    value => digit_char(value),
+                };
+                write!(f, " {}", ch)?;
+                if col % order == order - 1 {
+                    write!(f, " |")?;
+                }
+            }
+            writeln!(f)?;
+            if row % order == order - 1 {
+                writeln!(f, "{}", border)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{cell_name, SudokuGrid},
+        solver::Solver,
+    };
+
+    /// A solution of a grid of an order, shifting the digits of each row
+    fn pattern(order: usize) -> SizedGrid {
+        let side = order * order;
+        let mut grid = SizedGrid::new(order).unwrap();
+        for (row, col) in (0..side * side).map(|k| (k / side, k % side)) {
+            let value = (order * (row % order) + row / order + col) % side + 1;
+            assert!(grid.set(row, col, value as u8));
+        }
+        grid
+    }
+
+    #[test]
+    fn four_by_four_grids_parse_and_solve() {
+        let puzzle = SizedGrid::parse("1.3.\n.4..\n..4.\n.1.3\n").unwrap();
+        assert_eq!(puzzle.order(), 2);
+        assert_eq!(puzzle.side(), 4);
+        assert_eq!(puzzle.at(2, 2), 4);
+        let solution = puzzle.solve().unwrap();
+        assert!(solution.is_solution_of(&puzzle));
+        assert_eq!(
+            pattern(2).to_string(),
+            "+-----+-----+\n\
+             | 1 2 | 3 4 |\n\
+             | 3 4 | 1 2 |\n\
+             +-----+-----+\n\
+             | 2 3 | 4 1 |\n\
+             | 4 1 | 2 3 |\n\
+             +-----+-----+\n"
+        );
+        assert!(SizedGrid::parse("1..1\n....\n....\n....\n").is_err());
+    }
+
+    #[test]
+    fn sixteen_by_sixteen_grids_use_letters() {
+        let solution = pattern(4);
+        let compact = solution.to_compact_string();
+        assert!(compact.starts_with("123456789ABCDEFG56789ABCDEFG1234"));
+        // Keep every other cell as a clue
+        let puzzle: String = compact
+            .chars()
+            .enumerate()
+            .map(|(k, ch)| if k % 2 == 0 { ch } else { '0' })
+            .collect();
+        let puzzle = SizedGrid::parse(&puzzle.to_lowercase()).unwrap();
+        assert_eq!(puzzle.order(), 4);
+        assert_eq!(puzzle.at(0, 10), 11);
+        assert!(puzzle.solve().unwrap().is_solution_of(&puzzle));
+        assert!(SizedGrid::new(4).unwrap().solve().unwrap().is_complete());
+    }
+
+    #[test]
+    fn order_three_grids_agree_with_sudoku_grid() {
+        let compact =
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+        let mut sized = SizedGrid::parse(compact).unwrap();
+        let mut grid: SudokuGrid = compact.parse().unwrap();
+        assert_eq!(sized.order(), 3);
+        assert_eq!(sized.side(), 9);
+        assert_eq!(sized.to_string(), grid.to_string());
+        assert_eq!(sized.to_compact_string(), grid.to_compact_string());
+
+        // Both grids accept the same digits in every cell. The givens are locked in
+        // `SudokuGrid`, so only the digits placed here are unset again.
+        for (row, col) in (0..81).map(|k| (k / 9, k % 9)) {
+            assert_eq!(sized.at(row, col), grid.at(row, col));
+            assert_eq!(
+                sized.candidates_mask(row, col),
+                grid.candidates_mask(row, col) as u32
+            );
+            for value in 1..=9 {
+                let placed = sized.set(row, col, value);
+                assert_eq!(placed, grid.set(row, col, value), "{}", cell_name(row, col));
+                if placed {
+                    sized.unset(row, col);
+                    grid.unset(row, col);
+                }
+            }
+        }
+        assert_eq!(sized.to_compact_string(), compact);
+
+        let solution = Solver::from_grid(grid.clone()).solve().unwrap();
+        let sized_solution = sized.solve().unwrap();
+        assert_eq!(
+            sized_solution.to_compact_string(),
+            solution.to_compact_string()
+        );
+        assert_eq!(sized_solution.to_string(), solution.to_string());
+        assert!(sized_solution.is_complete() && !sized.is_complete());
+        assert!(sized_solution.is_solution_of(&sized) && solution.is_solution_of(&grid));
+        assert!(!sized.is_solution_of(&sized_solution) && !grid.is_solution_of(&solution));
+    }
+
+    #[test]
+    fn invalid_sized_grids_are_rejected() {
+        assert!(matches!(
+            SizedGrid::new(6),
+            Err(SudokuError::UnsupportedOrder(6))
+        ));
+        assert!(matches!(
+            SizedGrid::parse("123"),
+            Err(SudokuError::WrongCellCount(3))
+        ));
+        // 5 and E are not digits of a 4x4 grid, and H is not one of a 16x16 grid
+        assert!(matches!(
+            SizedGrid::parse("5..............."),
+            Err(SudokuError::InvalidChar {
+                ch: '5',
+                line: 1,
+                col: 1
+            })
+        ));
+        assert!(matches!(
+            SizedGrid::parse("1...\n..E.\n....\n....\n"),
+            Err(SudokuError::InvalidChar {
+                ch: 'E',
+                line: 2,
+                col: 3
+            })
+        ));
+        let sixteen = pattern(4).to_compact_string().replacen('G', "H", 1);
+        assert!(matches!(
+            SizedGrid::parse(&sixteen),
+            Err(SudokuError::InvalidChar { ch: 'H', .. })
+        ));
+        assert!(matches!(
+            SizedGrid::parse("*..............."),
+            Err(SudokuError::InvalidChar { ch: '*', .. })
+        ));
+        let mut grid = pattern(2);
+        assert!(!grid.set(0, 0, 1));
+        grid.unset(0, 0);
+        assert_eq!(grid.candidates_mask(0, 0), 0b0001);
+        assert!(!grid.set(0, 0, 2) && grid.set(0, 0, 1));
+    }
+}
@@ -1,77 +1,2339 @@
-use crate::input::SudokuGrid;
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+use crate::{
+    generator::mix64,
+    input::{cell_name, unit_cells, SudokuGrid, SQUARE_OF},
+    strategy::{
+        BoxLineReduction, CandidateGrid, HiddenSingles, NakedPairs, NakedSingles, PointingPairs,
+        Strategy, XWing,
+    },
+};
+
+/// Fill in forced cells without backtracking until the grid is solved,
+/// no more cells are forced, or a contradiction is reached.
+///
+/// A cell is forced when only one digit can be placed in it.
+/// Returns `Ok(true)` if the grid is solved, `Ok(false)` if no more cells are forced, and
+/// `Err((row, col))` with an empty cell that has no candidates left on a contradiction.
+/// The grid is left in the state reached when propagation stopped.
+pub fn propagate_to_fixpoint(grid: &mut SudokuGrid) -> Result<bool, (usize, usize)> {
+    fill_singles(grid, |_, _, _| {})
+}
+
+/// Repeatedly set every empty cell that has a single candidate, calling `on_set` for each
+/// placement, with the same result as `propagate_to_fixpoint`.
+fn fill_singles(
+    grid: &mut SudokuGrid,
+    mut on_set: impl FnMut(usize, usize, u8),
+) -> Result<bool, (usize, usize)> {
+    loop {
+        let mut solved = true;
+        let mut progress = false;
+        for r in 0..9 {
+            for c in 0..9 {
+                if grid.at(r, c) != 0 {
+                    continue;
+                }
+                let mask = grid.candidates_mask(r, c);
+                if mask == 0 {
+                    return Err((r, c));
+                }
+                if mask.count_ones() == 1 {
+                    let value = mask.trailing_zeros() as u8 + 1;
+                    if !grid.set(r, c, value) {
+                        return Err((r, c));
+                    }
+                    on_set(r, c, value);
+                    progress = true;
+                } else {
+                    solved = false;
+                }
+            }
+        }
+        if solved {
+            return Ok(true);
+        }
+        if !progress {
+            return Ok(false);
+        }
+    }
+}
+
+/// Check if a puzzle is proper, meaning that it has exactly one solution.
+/// Unsolvable puzzles and puzzles with several solutions are not proper.
+pub fn is_proper(grid: &SudokuGrid) -> bool {
+    Solver::from_grid(grid.clone()).has_unique_solution()
+}
+
+/// Check if a puzzle is proper and minimal, meaning that removing any of its
+/// clues would give a puzzle with more than one solution.
+pub fn is_proper_and_minimal(grid: &SudokuGrid) -> bool {
+    if !is_proper(grid) {
+        return false;
+    }
+    grid.iter_cells()
+        .filter(|&(_, _, value)| value != 0)
+        .all(|(row, col, _)| {
+            let mut reduced = grid.clone();
+            reduced.unset(row, col);
+            !is_proper(&reduced)
+        })
+}
+
+/// Solve each puzzle in turn, returning the solutions in the same order.
+/// Puzzles without a solution are returned as None.
+pub fn solve_many(puzzles: impl Iterator<Item = SudokuGrid>) -> Vec<Option<SudokuGrid>> {
+    puzzles
+        .map(|puzzle| Solver::from_grid(puzzle).solve())
+        .collect()
+}
+
+/// A value set by the solver, kept on a stack so that it can be reverted while backtracking
+struct Choice {
+    cell: (usize, usize),
+    value: u8,
+    /// Whether the value was forced by propagation rather than guessed.
+    /// Forced values are reverted when backtracking but no other value is tried for them.
+    forced: bool,
+}
+
+/// A solving technique.
+///
+/// Techniques are ordered by difficulty, from the easiest to the hardest,
+/// so the hardest technique a puzzle needs can be found with `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    /// A cell with a single candidate
+    NakedSingle,
+    /// A digit with a single possible cell in a row, column, or square
+    HiddenSingle,
+    /// Two cells of a unit that share the same two candidates
+    NakedPair,
+    /// A digit confined to one row or column within a square
+    PointingPair,
+    /// A digit confined to one square within a row or column
+    BoxLineReduction,
+    /// A digit confined to the same two columns in two rows, or vice versa
+    XWing,
+    /// Trying a value and backtracking if it fails
+    Guess,
+}
+
+/// The values placed and candidates eliminated by one application of a technique,
+/// as reported by `Solver::solve_with_techniques`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TechniqueStep {
+    pub technique: Technique,
+    /// Values placed in empty cells, as `(row, col, value)`
+    pub placements: Vec<(usize, usize, u8)>,
+    /// Candidates ruled out by the technique itself, as `(row, col, value)`.
+    /// Candidates of peers that are ruled out by the placements are not listed.
+    pub eliminations: Vec<(usize, usize, u8)>,
+}
+
+/// How hard a puzzle is for the technique-aware solver of `Solver::solve_with_techniques`.
+///
+/// Ratings are ordered from the easiest to the hardest: first by the hardest technique
+/// needed, then by the number of guesses, so puzzles can be sorted by their rating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rating {
+    /// The hardest technique needed, or None if the puzzle has no empty cells
+    pub hardest: Option<Technique>,
+    /// Number of guesses made when no technique applied, including the ones that were
+    /// taken back
+    pub guesses: usize,
+}
+
+impl Rating {
+    /// Get the difficulty tier of the rating.
+    pub fn difficulty(&self) -> Difficulty {
+        match (self.hardest, self.guesses) {
+            (None | Some(Technique::NakedSingle | Technique::HiddenSingle), _) => Difficulty::Easy,
+            (Some(Technique::Guess), guesses) if guesses > 1 => Difficulty::Expert,
+            (Some(Technique::XWing | Technique::Guess), _) => Difficulty::Hard,
+            _ => Difficulty::Medium,
+        }
+    }
+}
+
+/// A coarse difficulty tier of a puzzle, from its `Rating`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    /// Solved with singles alone
+    Easy,
+    /// Needs pairs, pointing pairs, or box-line reductions
+    Medium,
+    /// Needs an X-Wing or a single guess
+    Hard,
+    /// Needs more than one guess
+    Expert,
+}
+
+impl Difficulty {
+    /// Every tier, from the easiest to the hardest
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+    ];
+}
+
+/// The techniques of the technique-aware solver, from the easiest to the hardest
+const LADDER: [(Technique, &dyn Strategy); 6] = [
+    (Technique::NakedSingle, &NakedSingles),
+    (Technique::HiddenSingle, &HiddenSingles),
+    (Technique::NakedPair, &NakedPairs),
+    (Technique::PointingPair, &PointingPairs),
+    (Technique::BoxLineReduction, &BoxLineReduction),
+    (Technique::XWing, &XWing),
+];
+
+/// Apply the easiest technique of the ladder that makes progress.
+/// Returns what it did, or None if no technique applies.
+fn apply_easiest(grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> Option<TechniqueStep> {
+    let before_grid = grid.clone();
+    let before = cands.clone();
+    let (technique, _) = LADDER
+        .iter()
+        .find(|(_, strategy)| strategy.apply(grid, cands))?;
+
+    let mut step = TechniqueStep {
+        technique: *technique,
+        placements: Vec::new(),
+        eliminations: Vec::new(),
+    };
+    for (row, col) in before_grid.empty_cells() {
+        let value = grid.at(row, col);
+        if value != 0 {
+            step.placements.push((row, col, value));
+            continue;
+        }
+        // Leave out the candidates that the new placements rule out
+        let placed = before_grid.candidates_mask(row, col) & !grid.candidates_mask(row, col);
+        let removed = before.mask(row, col) & !cands.mask(row, col) & !placed;
+        for value in 1..=9u8 {
+            if removed & (1 << (value - 1)) != 0 {
+                step.eliminations.push((row, col, value));
+            }
+        }
+    }
+    Some(step)
+}
+
+/// Apply the easiest technique that makes progress until none applies,
+/// adding what each one did to `steps`.
+fn apply_techniques(
+    grid: &mut SudokuGrid,
+    cands: &mut CandidateGrid,
+    steps: &mut Vec<TechniqueStep>,
+) {
+    while let Some(step) = apply_easiest(grid, cands) {
+        steps.push(step);
+    }
+}
+
+/// Find the empty cell with the fewest candidates left, taking eliminations into account.
+/// Returns None if there are no empty cells.
+fn fewest_candidates(grid: &SudokuGrid, cands: &CandidateGrid) -> Option<(usize, usize)> {
+    grid.empty_cells()
+        .min_by_key(|&(row, col)| cands.mask(row, col).count_ones())
+}
+
+/// Count the ways to complete the values of a grid with only the row and column rules,
+/// ignoring squares and variants, stopping once `limit` completions are found.
+/// `rows` and `cols` hold the digits used in each row and column as bitmasks.
+/// The first completion found is stored in `first`.
+fn count_latin_completions(
+    values: &mut [[u8; 9]; 9],
+    rows: &mut [u16; 9],
+    cols: &mut [u16; 9],
+    limit: usize,
+    first: &mut Option<[[u8; 9]; 9]>,
+) -> usize {
+    // The empty cell with the fewest digits left in its row and column
+    let Some((row, col, mask)) = (0..81)
+        .map(|k| (k / 9, k % 9))
+        .filter(|&(row, col)| values[row][col] == 0)
+        .map(|(row, col)| (row, col, 0x1ff & !(rows[row] | cols[col])))
+        .min_by_key(|&(_, _, mask)| mask.count_ones())
+    else {
+        first.get_or_insert(*values);
+        return 1;
+    };
+    let mut count = 0;
+    for value in (1..=9u8).filter(|value| mask & (1 << (value - 1)) != 0) {
+        let bit = 1 << (value - 1);
+        values[row][col] = value;
+        rows[row] |= bit;
+        cols[col] |= bit;
+        count += count_latin_completions(values, rows, cols, limit - count, first);
+        values[row][col] = 0;
+        rows[row] &= !bit;
+        cols[col] &= !bit;
+        if count >= limit {
+            break;
+        }
+    }
+    count
+}
+
+/// A kind of unit a constraint can apply to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnitKind {
+    Row,
+    Col,
+}
+
+/// Extra rules on the cells of a grid, beyond the row, column and square rules,
+/// added to a solver with `Solver::with_constraint`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Constraint {
+    /// A sandwich sudoku clue: the sum of the digits placed between the 1 and the 9
+    /// of a row or column, given with a 0-based index.
+    Sandwich { unit: (UnitKind, usize), sum: u32 },
+    /// A comparison sudoku constraint: the digit in one cell must be greater than
+    /// the digit in another, usually adjacent, cell.
+    GreaterThan {
+        higher: (usize, usize),
+        lower: (usize, usize),
+    },
+    /// A thermometer: the digits must strictly increase along the path,
+    /// from the bulb at the first cell to the tip at the last one.
+    /// The cells of a path are usually adjacent but do not need to be.
+    Thermo(Vec<(usize, usize)>),
+    /// Every cell of a group must hold the same digit.
+    /// A clone region is written as one group per pair of matching cells of the regions.
+    EqualCells(Vec<Vec<(usize, usize)>>),
+}
+
+impl Constraint {
+    /// Check if the constraint can still be satisfied by the values of a grid,
+    /// which may be partially filled.
+    fn allows(&self, grid: &SudokuGrid) -> bool {
+        match self {
+            Constraint::Sandwich { unit, sum } => sandwich_allows(grid, *unit, *sum),
+            Constraint::GreaterThan { higher, lower } => greater_allows(grid, *higher, *lower),
+            Constraint::Thermo(path) => thermo_allows(grid, path),
+            Constraint::EqualCells(groups) => equal_cells_allow(grid, groups),
+        }
+    }
+}
+
+/// Check a sandwich clue against a grid.
+///
+/// The clue is only checked once both the 1 and the 9 of the unit are placed:
+/// the digits between them must not add up to more than the sum, and the sum must
+/// be reachable with distinct digits from 2 to 8 in the cells between them.
+fn sandwich_allows(grid: &SudokuGrid, unit: (UnitKind, usize), sum: u32) -> bool {
+    let values: [u8; 9] = match unit {
+        (UnitKind::Row, row) => *grid.row_slice(row),
+        (UnitKind::Col, col) => grid.col_values(col),
+    };
+    let one = values.iter().position(|&value| value == 1);
+    let nine = values.iter().position(|&value| value == 9);
+    let (Some(one), Some(nine)) = (one, nine) else {
+        return true;
+    };
+    let between = &values[one.min(nine) + 1..one.max(nine)];
+    let filled: u32 = between.iter().map(|&value| value as u32).sum();
+    if between.iter().all(|&value| value != 0) {
+        return filled == sum;
+    }
+    // The cells between hold distinct digits from 2 to 8
+    let len = between.len() as u32;
+    let min = (2..2 + len).sum::<u32>();
+    let max = (9 - len..9).sum::<u32>();
+    filled <= sum && (min..=max).contains(&sum)
+}
+
+/// Check a comparison between two cells against a grid.
+///
+/// When both cells are filled their digits are compared. When only one is filled,
+/// the other cell must still have a candidate on the right side of it.
+fn greater_allows(grid: &SudokuGrid, higher: (usize, usize), lower: (usize, usize)) -> bool {
+    match (grid.at(higher.0, higher.1), grid.at(lower.0, lower.1)) {
+        (0, 0) => true,
+        (0, low) => {
+            // Candidates above the lower digit
+            let above = 0x1ff & !((1 << low) - 1);
+            grid.candidates_mask(higher.0, higher.1) & above != 0
+        }
+        (high, 0) => {
+            // Candidates below the higher digit
+            let below = (1 << (high - 1)) - 1;
+            grid.candidates_mask(lower.0, lower.1) & below != 0
+        }
+        (high, low) => high > low,
+    }
+}
+
+/// Check a thermometer against a grid.
+///
+/// The cell at position `k` of a path of `n` cells needs room for the `k` smaller
+/// digits before it and the `n - 1 - k` larger digits after it, and a filled cell
+/// needs room for the cells between it and the other filled cells.
+/// An empty cell must still have a candidate in the range this leaves it.
+fn thermo_allows(grid: &SudokuGrid, path: &[(usize, usize)]) -> bool {
+    let n = path.len();
+    let values: Vec<u8> = path.iter().map(|&(row, col)| grid.at(row, col)).collect();
+    for k in 0..n {
+        // The digits the cell can hold given its position and the filled cells
+        let mut low = k as i32 + 1;
+        let mut high = 9 - (n - 1 - k) as i32;
+        for (other, &value) in values.iter().enumerate().filter(|&(other, _)| other != k) {
+            if value == 0 {
+                continue;
+            }
+            let gap = other as i32 - k as i32;
+            if gap < 0 {
+                low = low.max(value as i32 - gap);
+            } else {
+                high = high.min(value as i32 - gap);
+            }
+        }
+        if low > high {
+            return false;
+        }
+        let range = ((1u16 << high) - 1) & !((1u16 << (low - 1)) - 1);
+        let (row, col) = path[k];
+        let possible = match values[k] {
+            0 => grid.candidates_mask(row, col),
+            value => 1 << (value - 1),
+        };
+        if possible & range == 0 {
+            return false;
+        }
+    }
+    true
+}
+
+/// Check groups of equal cells against a grid.
+///
+/// The filled cells of a group must agree, and the empty ones must have a candidate
+/// in common with each other and with the filled digit if there is one.
+fn equal_cells_allow(grid: &SudokuGrid, groups: &[Vec<(usize, usize)>]) -> bool {
+    groups.iter().all(|group| {
+        let common = group.iter().fold(0x1ff, |common, &(row, col)| {
+            common
+                & match grid.at(row, col) {
+                    0 => grid.candidates_mask(row, col),
+                    value => 1 << (value - 1),
+                }
+        });
+        common != 0
+    })
+}
+
+/// The ways a solved grid can fail to match the expected solution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The puzzle has no solution
+    NoSolution,
+    /// The solution differs from the expected grid in these cells
+    Cells(Vec<(usize, usize)>),
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mismatch::NoSolution => write!(f, "no solution found"),
+            Mismatch::Cells(cells) => {
+                write!(f, "solution differs from the expected grid at")?;
+                for &(row, col) in cells {
+                    write!(f, " {}", cell_name(row, col))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// The order in which the solver picks the empty cell to guess next
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CellOrder {
+    /// The first empty cell in row-major order
+    RowMajor,
+    /// The empty cell with the fewest candidates (minimum remaining values),
+    /// taking the first one in row-major order on ties
+    #[default]
+    Mrv,
+    /// Like `Mrv`, breaking ties at random with the seed of the `SolverConfig`
+    MrvRandomTiebreak,
+    /// Like `Mrv`, breaking ties by the cell with the most empty peers (the degree
+    /// heuristic), since a guess there narrows down the most other cells
+    MostConstrainingVariable,
+}
+
+/// Settings of the backtracking search, so that heuristics can be compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SolverConfig {
+    /// How to pick the cell to guess
+    pub cell_order: CellOrder,
+    /// Whether to fill in the cells with a single candidate before every guess.
+    /// The strategies of the solver only run while propagating.
+    pub propagate: bool,
+    /// Seed of the random tie-breaks of `CellOrder::MrvRandomTiebreak`
+    pub seed: u64,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            cell_order: CellOrder::Mrv,
+            propagate: true,
+            seed: 0,
+        }
+    }
+}
 
 /// A Sudoku solver.
 ///
 /// The solver uses backtracking to find a solution to a Sudoku puzzle.
+/// With the default `SolverConfig`, before every guess it fills in the cells that have
+/// a single candidate, then branches on the empty cell with the fewest candidates.
 /// The solver is initialized with a Sudoku grid and can be used to find a solution
 /// The solver returns the solved grid if a solution is found, or None otherwise.
 pub struct Solver {
     grid: SudokuGrid,
+    stats: SolveStats,
+    /// Number of guesses currently on the choice stack
+    depth: usize,
+    /// Time after which the search gives up
+    deadline: Option<Instant>,
+    /// Number of guesses after which the search gives up
+    guess_limit: Option<u64>,
+    /// Whether the search gave up at the deadline or the guess limit
+    timed_out: bool,
+    /// The grid with the most filled cells reached so far, if it is tracked
+    best: Option<SudokuGrid>,
+    /// Grid whose values are tried first when guessing
+    template: Option<SudokuGrid>,
+    /// Changes made to the grid that have not been handed out yet, if they are recorded
+    steps: Option<VecDeque<SolveStep>>,
+    /// Extra constraints that solutions must satisfy as well
+    constraints: Vec<Constraint>,
+    /// Extra techniques applied after filling in single candidates
+    strategies: Vec<Box<dyn Strategy>>,
+    config: SolverConfig,
+}
+
+/// The error returned when solving takes longer than the allowed time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut;
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "timed out")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// The error returned by `Solver::solve_until_partial` when the search stops without a solution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialSolution {
+    /// The grid with the most filled cells that the search reached.
+    /// Its values are consistent with each other but may not lead to a solution.
+    pub best: Box<SudokuGrid>,
+    /// Whether the search gave up at the deadline, rather than finding no solution
+    pub timed_out: bool,
+}
+
+impl std::fmt::Display for PartialSolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let filled = 81 - self.best.empty_cells().count();
+        if self.timed_out {
+            write!(f, "timed out with {} cells filled", filled)
+        } else {
+            write!(f, "no solution, at most {} cells filled", filled)
+        }
+    }
+}
+
+impl std::error::Error for PartialSolution {}
+
+/// Counters describing how much work the solver did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Number of values guessed, including the values tried again while backtracking
+    pub guesses: u64,
+    /// Number of times the solver had to backtrack
+    pub backtracks: u64,
+    /// Largest number of guesses in effect at the same time
+    pub max_depth: usize,
 }
 
 impl Solver {
-    pub fn new(grid: SudokuGrid) -> Self {
-        Self { grid }
+    /// Create a solver from anything that can be converted into a grid,
+    /// such as puzzle text or a `[[u8; 9]; 9]` array.
+    /// Returns the conversion error if the input is not a valid grid.
+    pub fn new<G: TryInto<SudokuGrid>>(grid: G) -> Result<Self, G::Error> {
+        Ok(Self::from_grid(grid.try_into()?))
+    }
+
+    /// Create a solver for a grid.
+    pub fn from_grid(grid: SudokuGrid) -> Self {
+        Self {
+            grid,
+            stats: SolveStats::default(),
+            depth: 0,
+            deadline: None,
+            guess_limit: None,
+            timed_out: false,
+            best: None,
+            template: None,
+            steps: None,
+            constraints: Vec::new(),
+            strategies: Vec::new(),
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// Change the settings of the backtracking search.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Require solutions to also satisfy an extra constraint, such as a sandwich clue
+    /// or a thermometer. Placements that leave a constraint impossible are pruned
+    /// while searching.
+    pub fn with_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Add a strategy for the solver to apply while filling in forced cells.
+    ///
+    /// Strategies run in the order they were added, after the cells with a single
+    /// candidate have been filled in, and are repeated until none of them makes progress.
+    /// Cells left with a single candidate by their eliminations are then filled in too.
+    /// The candidates are rebuilt from the grid every time the strategies run, so
+    /// eliminations that did not lead to a placement are not kept.
+    pub fn with_strategy(mut self, strategy: impl Strategy + 'static) -> Self {
+        self.strategies.push(Box::new(strategy));
+        self
+    }
+
+    /// Solve the puzzle, returning the solved grid or None if there is no solution.
+    ///
+    /// A completely empty grid is a cheap case: it is filled without backtracking,
+    /// in at most 50 guesses with propagation placing the other digits.
+    pub fn solve(self) -> Option<SudokuGrid> {
+        self.solve_with_stats().0
     }
 
-    pub fn solve(mut self) -> Option<SudokuGrid> {
+    /// Solve the puzzle like `solve`, also returning how much work the search took.
+    /// The counters are deterministic, so they can be compared across puzzles.
+    pub fn solve_with_stats(mut self) -> (Option<SudokuGrid>, SolveStats) {
         // Keep track of choices that were made so that they could be reverted while backtracking
         let mut choices = Vec::new();
+        if self.search(&mut choices, false) {
+            (Some(self.grid), self.stats)
+        } else {
+            (None, self.stats)
+        }
+    }
+
+    /// Solve the puzzle like `solve`, giving up once the deadline has passed.
+    /// Returns an error if the search was stopped before it could finish.
+    pub fn solve_until(mut self, deadline: Instant) -> Result<Option<SudokuGrid>, TimedOut> {
+        self.deadline = Some(deadline);
+        let mut choices = Vec::new();
+        if self.search(&mut choices, false) {
+            Ok(Some(self.grid))
+        } else if self.timed_out {
+            Err(TimedOut)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Solve the puzzle like `solve_until`, returning the grid with the most filled cells
+    /// that the search reached if it stops without a solution, so that a timed-out search
+    /// still shows its progress.
+    pub fn solve_until_partial(mut self, deadline: Instant) -> Result<SudokuGrid, PartialSolution> {
+        self.deadline = Some(deadline);
+        self.best = Some(self.grid.clone());
+        let mut choices = Vec::new();
+        if self.search(&mut choices, false) {
+            return Ok(self.grid);
+        }
+        Err(PartialSolution {
+            // Safe to unwrap because tracking was turned on above
+            best: Box::new(self.best.take().unwrap()),
+            timed_out: self.timed_out,
+        })
+    }
+
+    /// Keep the current grid as the best partial solution if it has more filled cells
+    /// than the best one so far, when the best grid is tracked.
+    fn track_best(&mut self) {
+        if let Some(best) = &self.best {
+            if self.grid.empty_cells().count() < best.empty_cells().count() {
+                self.best = Some(self.grid.clone());
+            }
+        }
+    }
+
+    /// Solve the puzzle like `solve`, preferring the values of a template grid.
+    ///
+    /// When guessing a cell, the template's value for that cell is tried before the
+    /// other digits, so the solution agrees with the template wherever the puzzle allows.
+    /// Empty cells of the template fall back to the usual order.
+    /// The result is still a valid solution of the puzzle, or None if there is none.
+    pub fn solve_near(mut self, template: &SudokuGrid) -> Option<SudokuGrid> {
+        self.template = Some(template.clone());
+        self.solve()
+    }
+
+    /// Count the solutions of the puzzle, stopping once `limit` solutions have been found.
+    /// Returns 0 if the puzzle has no solution.
+    ///
+    /// Passing a limit of 2 is enough to tell whether the solution is unique.
+    pub fn count_solutions(mut self, limit: usize) -> usize {
+        let mut choices = Vec::new();
+        let mut count = 0;
+        while count < limit && self.search(&mut choices, count > 0) {
+            count += 1;
+        }
+        count
+    }
+
+    /// Find up to `limit` distinct solutions of the puzzle.
+    /// Returns an empty list if the puzzle has no solution.
+    pub fn sample_solutions(self, limit: usize) -> Vec<SudokuGrid> {
+        self.solutions().take(limit).collect()
+    }
+
+    /// Iterate over every distinct solution of the puzzle.
+    /// Solutions are found lazily, one backtracking search at a time, so only as many
+    /// solutions are computed as are taken from the iterator.
+    /// The iterator ends once the search space is exhausted.
+    pub fn solutions(mut self) -> impl Iterator<Item = SudokuGrid> {
+        let mut choices = Vec::new();
+        let mut found = false;
+        let mut exhausted = false;
+        std::iter::from_fn(move || {
+            if exhausted {
+                return None;
+            }
+            if self.search(&mut choices, found) {
+                found = true;
+                Some(self.grid.clone())
+            } else {
+                exhausted = true;
+                None
+            }
+        })
+    }
+
+    /// Check if the puzzle has exactly one solution.
+    pub fn has_unique_solution(self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
+    /// Check if the puzzle has exactly one solution like `has_unique_solution`, giving up
+    /// once the search has made `max_guesses` guesses.
+    /// Returns None if the search gave up before the answer was known.
+    pub fn has_unique_solution_within(mut self, max_guesses: u64) -> Option<bool> {
+        self.guess_limit = Some(max_guesses);
+        let mut choices = Vec::new();
+        let mut count = 0;
+        while count < 2 && self.search(&mut choices, count > 0) {
+            count += 1;
+        }
+        (!self.timed_out || count == 2).then_some(count == 1)
+    }
+
+    /// Check if the puzzle is degenerate: it has a unique solution that stays unique when
+    /// the square rules are dropped, so the rows and columns alone determine it.
+    pub fn boxes_redundant(&self) -> bool {
+        let mut values = [[0; 9]; 9];
+        let mut rows = [0u16; 9];
+        let mut cols = [0u16; 9];
+        for (row, col) in (0..81).map(|k| (k / 9, k % 9)) {
+            let value = self.grid.at(row, col);
+            if value != 0 {
+                values[row][col] = value;
+                rows[row] |= 1 << (value - 1);
+                cols[col] |= 1 << (value - 1);
+            }
+        }
+        let mut first = None;
+        if count_latin_completions(&mut values, &mut rows, &mut cols, 2, &mut first) != 1 {
+            return false;
+        }
+        // The only completion of the rows and columns must also follow the other rules
+        let Some(values) = first else {
+            return false;
+        };
+        let mut solution = self.grid.clone();
+        self.grid
+            .empty_cells()
+            .all(|(row, col)| solution.set(row, col, values[row][col]))
+            && self.grid_satisfies_clues(&solution)
+    }
+
+    /// Run the backtracking search until the grid holds a solution or the search space is exhausted.
+    /// Returns true if a solution was found.
+    ///
+    /// Cells with a single candidate are filled in before every guess, so only cells
+    /// with a real choice are guessed.
+    /// The choices that led to the solution are left on the stack, so calling this again
+    /// with `resume` set backtracks from the current solution and looks for the next one.
+    fn search(&mut self, choices: &mut Vec<Choice>, resume: bool) -> bool {
+        if resume && !self.backtrack(choices) {
+            return false;
+        }
         loop {
-            // Find an empty cell to make a choice
-            if let Some(empty_cell) = self.choose_empty_cell() {
-                let mut chosen = false;
-                // Try to set a value in the empty cell
-                // If a value is set, add the choice to the stack
-                for value in 1..=9 {
-                    let is_set = self.grid.set(empty_cell.0, empty_cell.1, value);
-                    if is_set {
-                        choices.push((empty_cell, value));
-                        chosen = true;
-                        break;
-                    }
+            if let Some(found) = self.step(choices) {
+                return found;
+            }
+        }
+    }
+
+    /// Run one iteration of the search: fill in forced cells, then guess a value for
+    /// the most constrained empty cell or backtrack.
+    /// Returns `Some(true)` if the grid holds a solution, `Some(false)` if the search
+    /// space is exhausted or the deadline has passed, and None if the search goes on.
+    fn step(&mut self, choices: &mut Vec<Choice>) -> Option<bool> {
+        self.track_best();
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self
+                .guess_limit
+                .is_some_and(|limit| self.stats.guesses >= limit)
+        {
+            self.timed_out = true;
+            return Some(false);
+        }
+        // Fill in forced cells, backtracking straight away on a contradiction
+        let propagated = !self.config.propagate || self.propagate(choices);
+        self.track_best();
+        if !propagated || !self.satisfies_clues() {
+            return if self.backtrack(choices) {
+                None
+            } else {
+                Some(false)
+            };
+        }
+        // Find the most constrained empty cell to make a choice
+        let Some(empty_cell) = self.choose_empty_cell() else {
+            // If there are no empty cells and the grid is valid, the grid is a solution
+            return Some(self.grid.is_valid());
+        };
+        // Try to set a value in the empty cell
+        // If a value is set, add the choice to the stack
+        if let Some(value) = self.set_next_value(empty_cell, None) {
+            choices.push(Choice {
+                cell: empty_cell,
+                value,
+                forced: false,
+            });
+            self.stats.guesses += 1;
+            self.depth += 1;
+            self.stats.max_depth = self.stats.max_depth.max(self.depth);
+            return None;
+        }
+        // If no value could be set, backtrack
+        if self.backtrack(choices) {
+            None
+        } else {
+            Some(false)
+        }
+    }
+
+    /// Fill in every empty cell that has a single candidate until there are none left,
+    /// pushing the placements onto the stack as forced choices.
+    /// The strategies of the solver are applied as well, if there are any.
+    /// Returns false if an empty cell without candidates was found.
+    fn propagate(&mut self, choices: &mut Vec<Choice>) -> bool {
+        loop {
+            let steps = &mut self.steps;
+            let filled = fill_singles(&mut self.grid, |row, col, value| {
+                choices.push(Choice {
+                    cell: (row, col),
+                    value,
+                    forced: true,
+                });
+                if let Some(steps) = steps {
+                    steps.push_back(SolveStep::Place { row, col, value });
                 }
-                if chosen {
+            });
+            match filled {
+                Ok(false) if !self.strategies.is_empty() => {}
+                result => return result.is_ok(),
+            }
+            match self.apply_strategies(choices) {
+                Some(true) => continue,
+                Some(false) => return true,
+                None => return false,
+            }
+        }
+    }
+
+    /// Apply the strategies until they make no more progress, then fill in the cells
+    /// left with a single candidate, pushing every placement onto the stack as a forced choice.
+    /// Returns whether any cell was filled in, or None if a contradiction was found.
+    fn apply_strategies(&mut self, choices: &mut Vec<Choice>) -> Option<bool> {
+        let before = self.grid.clone();
+        let mut cands = CandidateGrid::from_grid(&self.grid);
+        loop {
+            let mut progress = false;
+            for strategy in self.strategies.iter() {
+                progress |= strategy.apply(&mut self.grid, &mut cands);
+            }
+            if !progress {
+                break;
+            }
+        }
+
+        let mut contradiction = false;
+        for row in 0..9 {
+            for col in 0..9 {
+                if self.grid.at(row, col) != 0 {
                     continue;
                 }
-                // If no value could be set, backtrack
-                // Unset the last choice and try the next value
-                // If all values have been tried, backtrack further
-                'a: loop {
-                    let last_choice = choices.pop();
-                    if let Some((cell, mut value)) = last_choice {
-                        self.grid.unset(cell.0, cell.1);
-                        while value < 9 {
-                            let is_set = self.grid.set(cell.0, cell.1, value + 1);
-                            if is_set {
-                                choices.push((cell, value + 1));
-                                break 'a;
-                            } else {
-                                value += 1;
-                            }
-                        }
-                    } else {
-                        return None;
-                    }
+                let mask = cands.mask(row, col);
+                if mask.count_ones() == 1 {
+                    let value = mask.trailing_zeros() as u8 + 1;
+                    contradiction |= !cands.place(&mut self.grid, row, col, value);
+                } else if mask == 0 {
+                    contradiction = true;
                 }
-            } else if self.grid.is_valid() {
-                // If there are no empty cells and the grid is valid, return the solution
-                break;
-            } else {
-                return None;
             }
         }
-        Some(self.grid)
+
+        // Record the placements so that they are reverted when backtracking
+        let mut placed = false;
+        for row in 0..9 {
+            for col in 0..9 {
+                let value = self.grid.at(row, col);
+                if value != 0 && before.at(row, col) == 0 {
+                    choices.push(Choice {
+                        cell: (row, col),
+                        value,
+                        forced: true,
+                    });
+                    self.record(SolveStep::Place { row, col, value });
+                    placed = true;
+                }
+            }
+        }
+        if contradiction {
+            None
+        } else {
+            Some(placed)
+        }
     }
 
-    fn choose_empty_cell(&self) -> Option<(usize, usize)> {
-        for r in 0..9 {
-            for c in 0..9 {
-                if self.grid.at(r, c) == 0 {
-                    return Some((r, c));
+    /// Check if the extra clues can still be satisfied by the current grid.
+    fn satisfies_clues(&self) -> bool {
+        self.grid_satisfies_clues(&self.grid)
+    }
+
+    /// Check if the extra clues can still be satisfied by a grid.
+    fn grid_satisfies_clues(&self, grid: &SudokuGrid) -> bool {
+        self.constraints
+            .iter()
+            .all(|constraint| constraint.allows(grid))
+    }
+
+    /// Unset the last guess and try its next value, unsetting any forced choices on the way.
+    /// If all values of the guess have been tried, backtrack further.
+    /// Returns false if there are no choices left to revisit.
+    fn backtrack(&mut self, choices: &mut Vec<Choice>) -> bool {
+        self.stats.backtracks += 1;
+        while let Some(Choice {
+            cell,
+            value,
+            forced,
+        }) = choices.pop()
+        {
+            self.grid.unset(cell.0, cell.1);
+            self.record(SolveStep::Remove {
+                row: cell.0,
+                col: cell.1,
+            });
+            if forced {
+                continue;
+            }
+            self.depth -= 1;
+            if let Some(value) = self.set_next_value(cell, Some(value)) {
+                choices.push(Choice {
+                    cell,
+                    value,
+                    forced: false,
+                });
+                self.stats.guesses += 1;
+                self.depth += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Set the first value that can be placed in an empty cell, trying the values
+    /// in the order given by `value_order` and starting after `last` if given.
+    /// Returns the value that was set, or None if no remaining value fits.
+    fn set_next_value(&mut self, cell: (usize, usize), last: Option<u8>) -> Option<u8> {
+        let order = self.value_order(cell);
+        let start = match last {
+            Some(last) => order.iter().position(|&value| value == last)? + 1,
+            None => 0,
+        };
+        let value = order[start..]
+            .iter()
+            .copied()
+            .find(|&value| self.grid.set(cell.0, cell.1, value))?;
+        self.record(SolveStep::Place {
+            row: cell.0,
+            col: cell.1,
+            value,
+        });
+        Some(value)
+    }
+
+    /// Record a change to the grid if steps are being recorded.
+    fn record(&mut self, step: SolveStep) {
+        if let Some(steps) = &mut self.steps {
+            steps.push_back(step);
+        }
+    }
+
+    /// The order in which values are guessed for a cell: the template's value first
+    /// if there is one, then the remaining digits in increasing order.
+    fn value_order(&self, cell: (usize, usize)) -> [u8; 9] {
+        let mut order = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let preferred = self
+            .template
+            .as_ref()
+            .map_or(0, |template| template.at(cell.0, cell.1));
+        if preferred != 0 {
+            // Move the preferred value to the front, keeping the others in order
+            order[..preferred as usize].rotate_right(1);
+        }
+        order
+    }
+
+    /// Explain why an empty cell of the current grid must hold its value.
+    ///
+    /// The reason is either that the value is the only candidate left in the cell
+    /// (a naked single), or that the cell is the only place left for the value in
+    /// its row, column, or square (a hidden single).
+    /// Returns None if the cell is filled or its value is not forced yet.
+    pub fn explain_cell(&self, cell: (usize, usize)) -> Option<String> {
+        let (row, col) = cell;
+        let mask = self.grid.candidates_mask(row, col);
+        if mask == 0 {
+            return None;
+        }
+        if mask.count_ones() == 1 {
+            return Some(format!(
+                "{} must be {}: it is the only candidate left after removing the digits in its row, column and square",
+                cell_name(row, col),
+                mask.trailing_zeros() + 1
+            ));
+        }
+
+        let square = SQUARE_OF[row][col];
+        let units = [
+            (format!("row {}", row + 1), row),
+            (format!("column {}", col + 1), 9 + col),
+            (format!("square {}", square + 1), 18 + square),
+        ];
+        for value in self.grid.candidates(row, col) {
+            let bit = 1 << (value - 1);
+            for (name, unit) in units.iter() {
+                let elsewhere = unit_cells(*unit).any(|other| {
+                    other != cell && self.grid.candidates_mask(other.0, other.1) & bit != 0
+                });
+                if !elsewhere {
+                    return Some(format!(
+                        "{} must be {}: it is the only cell in {} where {} can go",
+                        cell_name(row, col),
+                        value,
+                        name,
+                        value
+                    ));
                 }
             }
         }
         None
     }
+
+    /// Solve the puzzle like a person would, applying the easiest technique that makes
+    /// progress at each step, in the order of `Technique`, and only guessing when no
+    /// technique applies.
+    ///
+    /// A guess tries each candidate of the empty cell with the fewest candidates in turn,
+    /// and is taken back if the techniques and later guesses run into a contradiction.
+    /// The steps only hold the guesses that led to the solution.
+    /// Returns the solution and the steps that led to it, or None if there is no solution.
+    pub fn solve_with_techniques(self) -> Option<(SudokuGrid, Vec<TechniqueStep>)> {
+        self.technique_solve()
+            .map(|(solution, steps, _)| (solution, steps))
+    }
+
+    /// Solve the puzzle like `solve_with_techniques`, also returning the number of guesses
+    /// that were made, including the ones that were taken back.
+    fn technique_solve(&self) -> Option<(SudokuGrid, Vec<TechniqueStep>, usize)> {
+        let mut steps = Vec::new();
+        let mut guesses = 0;
+        let cands = CandidateGrid::from_grid(&self.grid);
+        let solution = self.technique_search(self.grid.clone(), cands, &mut steps, &mut guesses)?;
+        Some((solution, steps, guesses))
+    }
+
+    /// Apply the techniques to a grid, then guess each candidate of the empty cell with
+    /// the fewest candidates and search again from there, until the grid is solved.
+    /// The steps of the guesses that did not lead to a solution are removed from `steps`.
+    /// Returns None if there is no solution from this grid.
+    fn technique_search(
+        &self,
+        mut grid: SudokuGrid,
+        mut cands: CandidateGrid,
+        steps: &mut Vec<TechniqueStep>,
+        guesses: &mut usize,
+    ) -> Option<SudokuGrid> {
+        let start = steps.len();
+        apply_techniques(&mut grid, &mut cands, steps);
+        let Some((row, col)) = fewest_candidates(&grid, &cands) else {
+            if self.grid_satisfies_clues(&grid) {
+                return Some(grid);
+            }
+            steps.truncate(start);
+            return None;
+        };
+        let mask = cands.mask(row, col);
+        for value in (1..=9u8).filter(|value| mask & (1 << (value - 1)) != 0) {
+            *guesses += 1;
+            let (mut next, mut next_cands) = (grid.clone(), cands.clone());
+            if !next_cands.place(&mut next, row, col, value) {
+                continue;
+            }
+            steps.push(TechniqueStep {
+                technique: Technique::Guess,
+                placements: vec![(row, col, value)],
+                eliminations: Vec::new(),
+            });
+            if let Some(solution) = self.technique_search(next, next_cands, steps, guesses) {
+                return Some(solution);
+            }
+            steps.pop();
+        }
+        steps.truncate(start);
+        None
+    }
+
+    /// Solve the puzzle like `solve_with_techniques`, returning the distinct techniques
+    /// that were needed from the easiest to the hardest.
+    /// The last technique is the hardest one, which gives the difficulty of the puzzle.
+    /// Returns no solution and no techniques if the puzzle has no solution.
+    pub fn techniques_used(self) -> (Option<SudokuGrid>, Vec<Technique>) {
+        let Some((solution, steps)) = self.solve_with_techniques() else {
+            return (None, Vec::new());
+        };
+        let mut techniques: Vec<Technique> = steps.iter().map(|step| step.technique).collect();
+        techniques.sort_unstable();
+        techniques.dedup();
+        (Some(solution), techniques)
+    }
+
+    /// Solve the puzzle like `solve_with_techniques`, recording the technique that placed
+    /// the value of every cell that was empty in the puzzle, or `Technique::Guess` if it
+    /// was guessed. Givens have no technique.
+    /// Returns None if there is no solution.
+    pub fn solve_with_attribution(self) -> Option<(SudokuGrid, [[Option<Technique>; 9]; 9])> {
+        let (solution, steps) = self.solve_with_techniques()?;
+        let mut placed_by = [[None; 9]; 9];
+        for step in steps {
+            for (row, col, _) in step.placements {
+                placed_by[row][col] = Some(step.technique);
+            }
+        }
+        Some((solution, placed_by))
+    }
+
+    /// Estimate the smallest number of guesses needed to solve the puzzle, where 0 means
+    /// that it can be solved with techniques alone.
+    ///
+    /// This is the number of guesses left in the steps of `solve_with_techniques`, which
+    /// only guesses when no technique applies and then picks the cell with the fewest
+    /// candidates, so it is an upper bound rather than the minimum over every solving order.
+    /// A puzzle without a solution needs no guesses.
+    pub fn min_guesses(&self) -> usize {
+        self.technique_solve().map_or(0, |(_, steps, _)| {
+            steps
+                .iter()
+                .filter(|step| step.technique == Technique::Guess)
+                .count()
+        })
+    }
+
+    /// Rate the difficulty of the puzzle from the search of `solve_with_techniques`,
+    /// counting every guess it made, including the ones that were taken back.
+    /// Returns None if there is no solution.
+    pub fn rate(&self) -> Option<Rating> {
+        let (_, steps, guesses) = self.technique_solve()?;
+        Some(Rating {
+            hardest: steps.iter().map(|step| step.technique).max(),
+            guesses,
+        })
+    }
+
+    /// Find the first cell the technique-aware solver of `solve_with_techniques` has to
+    /// guess, which is the empty cell with the fewest candidates once the techniques run out.
+    /// Returns None if the puzzle can be solved with techniques alone or has no solution.
+    pub fn first_guess_cell(&self) -> Option<(usize, usize)> {
+        let (_, steps, _) = self.technique_solve()?;
+        let guess = steps
+            .iter()
+            .find(|step| step.technique == Technique::Guess)?;
+        guess.placements.first().map(|&(row, col, _)| (row, col))
+    }
+
+    /// Solve the puzzle and return the solution behind an `Arc`,
+    /// so that it can be shared without copying the grid.
+    pub fn solve_arc(self) -> Option<Arc<SudokuGrid>> {
+        self.solve().map(Arc::new)
+    }
+
+    /// Solve the puzzle and compare the solution against an expected grid.
+    /// Returns the mismatch if there is no solution or it differs from the expected grid.
+    pub fn solve_expecting(self, expected: &SudokuGrid) -> Result<(), Mismatch> {
+        let solution = self.solve().ok_or(Mismatch::NoSolution)?;
+        let cells = solution.diff(expected);
+        if cells.is_empty() {
+            Ok(())
+        } else {
+            Err(Mismatch::Cells(cells))
+        }
+    }
+
+    /// Choose the empty cell to guess in the order of the configuration, by default the
+    /// one with the fewest candidates (minimum remaining values).
+    /// A cell without candidates is returned immediately, since no value can be set in it
+    /// and the solver has to backtrack.
+    /// Returns None if there are no empty cells.
+    fn choose_empty_cell(&self) -> Option<(usize, usize)> {
+        if self.config.cell_order == CellOrder::RowMajor {
+            return self.grid.empty_cells().next();
+        }
+        let mut best: Option<((usize, usize), (u32, u64))> = None;
+        for (r, c) in self.grid.empty_cells() {
+            let count = self.grid.candidates_mask(r, c).count_ones();
+            if count == 0 {
+                return Some((r, c));
+            }
+            let key = (count, self.tiebreak((r, c)));
+            if best.is_none_or(|(_, best_key)| key < best_key) {
+                best = Some(((r, c), key));
+            }
+        }
+        best.map(|(cell, _)| cell)
+    }
+
+    /// Rank cells with the same number of candidates, where the lowest rank is chosen.
+    /// The random ranks change with every guess, so ties are not always broken the same way.
+    fn tiebreak(&self, cell: (usize, usize)) -> u64 {
+        match self.config.cell_order {
+            CellOrder::MrvRandomTiebreak => {
+                let counter = self.stats.guesses * 81 + (cell.0 * 9 + cell.1) as u64;
+                mix64(self.config.seed ^ mix64(counter))
+            }
+            CellOrder::MostConstrainingVariable => u64::MAX - self.empty_peers(cell) as u64,
+            _ => 0,
+        }
+    }
+
+    /// Count the empty cells other than `cell` in its row, column, and square.
+    fn empty_peers(&self, cell: (usize, usize)) -> usize {
+        let (row, col) = cell;
+        let square = SQUARE_OF[row][col];
+        self.grid
+            .empty_cells()
+            .filter(|&(r, c)| (r, c) != cell && (r == row || c == col || SQUARE_OF[r][c] == square))
+            .count()
+    }
+}
+
+/// A single change made to the grid while solving, as reported by `StepSolver`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolveStep {
+    /// A value was placed in an empty cell, either because it was forced or as a guess
+    Place { row: usize, col: usize, value: u8 },
+    /// The value of a cell was removed while backtracking
+    Remove { row: usize, col: usize },
+    /// The grid holds a solution
+    Done,
+    /// The puzzle has no solution
+    Failed,
+}
+
+/// A solver that reports every change it makes to the grid, one step at a time.
+///
+/// The steps are produced lazily by iterating over the solver, ending with
+/// `SolveStep::Done` or `SolveStep::Failed`.
+/// Applying the steps to the puzzle in order leaves it in the same state as
+/// `Solver::solve`: the solution, or the original puzzle if there is no solution.
+pub struct StepSolver {
+    solver: Solver,
+    choices: Vec<Choice>,
+    finished: bool,
+}
+
+impl StepSolver {
+    /// Create a step solver for a grid.
+    pub fn new(grid: SudokuGrid) -> Self {
+        let mut solver = Solver::from_grid(grid);
+        solver.steps = Some(VecDeque::new());
+        Self {
+            solver,
+            choices: Vec::new(),
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for StepSolver {
+    type Item = SolveStep;
+
+    fn next(&mut self) -> Option<SolveStep> {
+        loop {
+            if let Some(step) = self.solver.steps.as_mut().and_then(VecDeque::pop_front) {
+                return Some(step);
+            }
+            if self.finished {
+                return None;
+            }
+            if let Some(found) = self.solver.step(&mut self.choices) {
+                self.finished = true;
+                self.solver.record(if found {
+                    SolveStep::Done
+                } else {
+                    SolveStep::Failed
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::input::{SudokuError, Variant};
+
+    /// The puzzle from the Wikipedia article on Sudoku
+    const PUZZLE: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    /// The solution of `PUZZLE`
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    /// `SOLUTION` with a rectangle of four cells removed, which can be filled in two ways
+    const TWO_SOLUTIONS: &str =
+        "5346789126721953..1983425678597614234268537917139248569615372..287419635345286179";
+
+    /// A 17-clue puzzle that needs a lot of guessing
+    const HARD: &str =
+        "000000010400000000020000000000050407008000300001090000300400200050100000000806000";
+
+    /// A puzzle that cannot be solved with the techniques of `Technique` alone
+    const GUESSING: &str =
+        ".....1..74......39.21...4..94.......1..9...85.5..3....8...4....6....8..2....25.6.";
+
+    /// `GUESSING` with a wrong 5 in R7C3, which the techniques alone do not rule out
+    const WRONG_GUESS: &str =
+        ".....1..74......39.21...4..94.......1..9...85.5..3....8.5.4....6....8..2....25.6.";
+
+    /// "AI Escargot", published by Arto Inkala in 2006 as the most difficult Sudoku
+    const AI_ESCARGOT: &str =
+        "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..";
+
+    /// The puzzle published by Arto Inkala in 2012 as the world's hardest Sudoku
+    const INKALA_2012: &str =
+        "8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..";
+
+    /// "Easter Monster", posted on the Sudoku Players' Forums in 2007
+    const EASTER_MONSTER: &str =
+        "1.......2.9.4...5...6...7...5.9.3.......7.......85..4.7.....6...3...9.8...2.....1";
+
+    /// A puzzle that needs pointing pairs
+    const POINTING: &str =
+        "......3.7..1..6.457.5.9..122..6....43...79.......48...5.2...86......5..1...9.....";
+
+    fn grid(puzzle: &str) -> SudokuGrid {
+        puzzle.parse().unwrap()
+    }
+
+    /// Apply the techniques to a puzzle until none applies, without guessing
+    fn stuck(puzzle: &str) -> (SudokuGrid, Vec<TechniqueStep>) {
+        let mut grid = grid(puzzle);
+        let mut cands = CandidateGrid::from_grid(&grid);
+        let mut steps = Vec::new();
+        apply_techniques(&mut grid, &mut cands, &mut steps);
+        (grid, steps)
+    }
+
+    /// A strategy that counts how often it runs without changing anything
+    struct Counting(Rc<Cell<usize>>);
+
+    impl Strategy for Counting {
+        fn apply(&self, _grid: &mut SudokuGrid, _cands: &mut CandidateGrid) -> bool {
+            self.0.set(self.0.get() + 1);
+            false
+        }
+    }
+
+    #[test]
+    fn custom_strategies_run_while_propagating() {
+        let runs = Rc::new(Cell::new(0));
+        let solution = Solver::from_grid(grid(HARD))
+            .with_strategy(Counting(runs.clone()))
+            .solve()
+            .unwrap();
+        assert!(runs.get() > 0);
+        assert!(solution.is_solution_of(&grid(HARD)));
+    }
+
+    #[test]
+    fn strategies_do_not_change_the_solution() {
+        let plain = Solver::from_grid(grid(HARD)).solve_with_stats();
+        let with_strategy = Solver::from_grid(grid(HARD))
+            .with_strategy(HiddenSingles)
+            .solve_with_stats();
+        assert_eq!(plain.0, with_strategy.0);
+        assert!(with_strategy.1.guesses < plain.1.guesses);
+    }
+
+    #[test]
+    fn explain_cell_names_the_unit() {
+        let solver = Solver::from_grid(grid(PUZZLE));
+        assert_eq!(
+            solver.explain_cell((4, 4)).unwrap(),
+            "R5C5 must be 5: it is the only candidate left after removing the digits in its row, column and square"
+        );
+        assert_eq!(
+            solver.explain_cell((6, 0)).unwrap(),
+            "R7C1 must be 9: it is the only cell in column 1 where 9 can go"
+        );
+        assert_eq!(
+            solver.explain_cell((8, 6)).unwrap(),
+            "R9C7 must be 1: it is the only cell in square 9 where 1 can go"
+        );
+        assert!(solver.explain_cell((0, 0)).is_none());
+    }
+
+    #[test]
+    fn empty_grid_is_solved_without_backtracking() {
+        let (solution, stats) = Solver::from_grid(SudokuGrid::default()).solve_with_stats();
+        assert!(solution.unwrap().is_solution_of(&SudokuGrid::default()));
+        assert_eq!(stats.backtracks, 0);
+        // Every guess is a placement, and propagation fills in the rest
+        assert!(stats.guesses <= 50, "{} guesses", stats.guesses);
+        assert_eq!(stats.max_depth as u64, stats.guesses);
+    }
+
+    #[test]
+    fn puzzles_with_blank_rows_solve_promptly() {
+        // Blank out whole rows of the puzzle, up to an empty grid
+        for blank in [3, 6, 9] {
+            let mut puzzle = grid(PUZZLE);
+            for row in 0..blank {
+                for col in 0..9 {
+                    puzzle.unset(row, col);
+                }
+            }
+            let (solution, stats) = Solver::from_grid(puzzle.clone()).solve_with_stats();
+            assert!(solution.unwrap().is_solution_of(&puzzle));
+            assert!(stats.guesses <= 100, "{} guesses", stats.guesses);
+        }
+    }
+
+    #[test]
+    fn cells_without_candidates_stop_the_search() {
+        // R1C9 can only hold 9, which is already in its column
+        let puzzle = grid(
+            "12345678.........9...............................................................",
+        );
+        assert_eq!(puzzle.candidates_mask(0, 8), 0);
+        let (solution, stats) = Solver::from_grid(puzzle).solve_with_stats();
+        assert!(solution.is_none());
+        assert_eq!(stats.guesses, 0);
+    }
+
+    #[test]
+    fn propagate_to_fixpoint_fills_forced_cells() {
+        let mut easy = grid(PUZZLE);
+        assert_eq!(propagate_to_fixpoint(&mut easy), Ok(true));
+        assert_eq!(easy, grid(SOLUTION));
+
+        let mut hard = grid(HARD);
+        assert_eq!(propagate_to_fixpoint(&mut hard), Ok(false));
+        assert!(hard.empty_cells().count() > 0);
+    }
+
+    #[test]
+    fn propagate_to_fixpoint_reports_a_contradiction() {
+        let mut stuck =
+            SudokuGrid::from_triples((0..8).map(|col| (0, col, col as u8 + 1)).chain([(1, 8, 9)]))
+                .unwrap();
+        assert_eq!(propagate_to_fixpoint(&mut stuck), Err((0, 8)));
+    }
+
+    #[test]
+    fn solve_expecting_compares_the_solution() {
+        assert_eq!(
+            Solver::from_grid(grid(PUZZLE)).solve_expecting(&grid(SOLUTION)),
+            Ok(())
+        );
+
+        let mismatch = Solver::from_grid(grid(PUZZLE))
+            .solve_expecting(&grid(PUZZLE))
+            .unwrap_err();
+        let Mismatch::Cells(cells) = &mismatch else {
+            panic!("expected differing cells, got {:?}", mismatch);
+        };
+        assert_eq!(cells.len(), grid(PUZZLE).empty_cells().count());
+        assert!(mismatch
+            .to_string()
+            .starts_with("solution differs from the expected grid at R1C3 R1C4 "));
+
+        let mut stuck = grid(PUZZLE);
+        stuck.lock(0, 2);
+        assert_eq!(
+            Solver::from_grid(stuck).solve_expecting(&grid(SOLUTION)),
+            Err(Mismatch::NoSolution)
+        );
+    }
+
+    #[test]
+    fn solve_arc_shares_the_solution() {
+        let solution = Solver::from_grid(grid(PUZZLE)).solve_arc().unwrap();
+        let shared = Arc::clone(&solution);
+        assert_eq!(*shared, grid(SOLUTION));
+        assert_eq!(Arc::strong_count(&solution), 2);
+    }
+
+    #[test]
+    fn solver_new_accepts_text_and_arrays() {
+        let from_text = Solver::new(PUZZLE).unwrap().solve();
+        assert_eq!(from_text, Some(grid(SOLUTION)));
+
+        let mut cells = [[0; 9]; 9];
+        for (row, col, value) in grid(PUZZLE).iter_cells() {
+            cells[row][col] = value;
+        }
+        assert_eq!(Solver::new(cells).unwrap().solve(), from_text);
+
+        assert!(Solver::new("not a puzzle").is_err());
+        cells[0][2] = 5;
+        assert!(matches!(
+            Solver::new(cells),
+            Err(SudokuError::InvalidPuzzle { .. })
+        ));
+    }
+
+    #[test]
+    fn solutions_are_counted_up_to_the_limit() {
+        assert_eq!(Solver::from_grid(grid(PUZZLE)).count_solutions(10), 1);
+        assert_eq!(
+            Solver::from_grid(grid(TWO_SOLUTIONS)).count_solutions(10),
+            2
+        );
+        assert_eq!(Solver::from_grid(grid(TWO_SOLUTIONS)).count_solutions(1), 1);
+        assert_eq!(
+            Solver::from_grid(SudokuGrid::default()).count_solutions(5),
+            5
+        );
+
+        let mut stuck = grid(PUZZLE);
+        stuck.lock(0, 2);
+        assert_eq!(Solver::from_grid(stuck).count_solutions(10), 0);
+    }
+
+    #[test]
+    fn unique_solutions_are_detected() {
+        assert!(Solver::from_grid(grid(PUZZLE)).has_unique_solution());
+        assert!(Solver::from_grid(grid(SOLUTION)).has_unique_solution());
+        assert!(!Solver::from_grid(grid(TWO_SOLUTIONS)).has_unique_solution());
+        assert!(!Solver::from_grid(SudokuGrid::default()).has_unique_solution());
+    }
+
+    #[test]
+    fn sample_solutions_are_distinct() {
+        let puzzle = grid(TWO_SOLUTIONS);
+        let solutions = Solver::from_grid(puzzle.clone()).sample_solutions(5);
+        assert_eq!(solutions.len(), 2);
+        assert_ne!(solutions[0], solutions[1]);
+        assert!(solutions
+            .iter()
+            .all(|solution| solution.is_solution_of(&puzzle)));
+        assert!(solutions.contains(&grid(SOLUTION)));
+
+        let solutions = Solver::from_grid(SudokuGrid::default()).sample_solutions(3);
+        assert_eq!(solutions.len(), 3);
+        assert!(solutions[0] != solutions[1] && solutions[1] != solutions[2]);
+        assert!(Solver::from_grid(grid(TWO_SOLUTIONS))
+            .sample_solutions(0)
+            .is_empty());
+    }
+
+    #[test]
+    fn guesses_are_made_in_the_most_constrained_cell() {
+        let puzzle = grid(HARD);
+        let fewest = puzzle
+            .empty_cells()
+            .map(|(r, c)| puzzle.candidates_mask(r, c).count_ones())
+            .min();
+        let (r, c) = Solver::from_grid(puzzle.clone())
+            .choose_empty_cell()
+            .unwrap();
+        assert_eq!(Some(puzzle.candidates_mask(r, c).count_ones()), fewest);
+
+        let stuck =
+            SudokuGrid::from_triples((0..8).map(|col| (0, col, col as u8 + 1)).chain([(1, 8, 9)]))
+                .unwrap();
+        assert_eq!(Solver::from_grid(stuck).choose_empty_cell(), Some((0, 8)));
+        assert_eq!(Solver::from_grid(grid(SOLUTION)).choose_empty_cell(), None);
+    }
+
+    #[test]
+    fn easy_puzzles_need_no_guesses() {
+        let (solution, stats) = Solver::from_grid(grid(PUZZLE)).solve_with_stats();
+        assert_eq!(solution, Some(grid(SOLUTION)));
+        assert_eq!(stats, SolveStats::default());
+    }
+
+    #[test]
+    fn contradictions_found_by_propagation_are_unsolvable() {
+        let stuck =
+            SudokuGrid::from_triples((0..8).map(|col| (0, col, col as u8 + 1)).chain([(1, 8, 9)]))
+                .unwrap();
+        let (solution, stats) = Solver::from_grid(stuck).solve_with_stats();
+        assert_eq!(solution, None);
+        assert_eq!(stats.guesses, 0);
+    }
+
+    #[test]
+    fn solve_stats_are_deterministic() {
+        let (solution, stats) = Solver::from_grid(grid(HARD)).solve_with_stats();
+        assert!(solution.unwrap().is_solution_of(&grid(HARD)));
+        assert_eq!(
+            stats,
+            SolveStats {
+                guesses: 1456,
+                backtracks: 723,
+                max_depth: 21
+            }
+        );
+        assert_eq!(Solver::from_grid(grid(HARD)).solve_with_stats().1, stats);
+        assert!(stats.max_depth as u64 <= stats.guesses);
+    }
+
+    #[test]
+    fn solve_until_gives_up_after_the_deadline() {
+        let past = Instant::now();
+        assert_eq!(
+            Solver::from_grid(grid(HARD)).solve_until(past),
+            Err(TimedOut)
+        );
+
+        let future = Instant::now() + std::time::Duration::from_secs(60);
+        let solution = Solver::from_grid(grid(PUZZLE)).solve_until(future);
+        assert_eq!(solution, Ok(Some(grid(SOLUTION))));
+        let mut stuck = grid(PUZZLE);
+        stuck.lock(0, 2);
+        assert_eq!(Solver::from_grid(stuck).solve_until(future), Ok(None));
+    }
+
+    #[test]
+    fn explanations_agree_with_the_solution() {
+        let solver = Solver::from_grid(grid(PUZZLE));
+        let solution = grid(SOLUTION);
+        let mut explained = 0;
+        for (row, col) in grid(PUZZLE).empty_cells() {
+            if let Some(reason) = solver.explain_cell((row, col)) {
+                let expected =
+                    format!("{} must be {}:", cell_name(row, col), solution.at(row, col));
+                assert!(reason.starts_with(&expected), "{}", reason);
+                explained += 1;
+            }
+        }
+        assert!(explained > 0);
+        assert!(Solver::from_grid(SudokuGrid::default())
+            .explain_cell((4, 4))
+            .is_none());
+    }
+
+    #[test]
+    fn techniques_are_ordered_by_difficulty() {
+        assert!(Technique::NakedSingle < Technique::Guess);
+        assert!(Technique::NakedSingle < Technique::HiddenSingle);
+        assert!(Technique::PointingPair < Technique::XWing);
+        let hardest = [
+            Technique::HiddenSingle,
+            Technique::XWing,
+            Technique::NakedPair,
+        ]
+        .into_iter()
+        .max();
+        assert_eq!(hardest, Some(Technique::XWing));
+    }
+
+    #[test]
+    fn solve_near_prefers_the_template() {
+        let solutions = Solver::from_grid(grid(TWO_SOLUTIONS)).sample_solutions(2);
+        for solution in &solutions {
+            let near = Solver::from_grid(grid(TWO_SOLUTIONS)).solve_near(solution);
+            assert_eq!(near.as_ref(), Some(solution));
+        }
+
+        let template = solutions[1].clone();
+        let near = Solver::from_grid(SudokuGrid::default())
+            .solve_near(&template)
+            .unwrap();
+        assert_eq!(near, template);
+        assert_eq!(
+            Solver::from_grid(grid(PUZZLE)).solve_near(&SudokuGrid::default()),
+            Some(grid(SOLUTION))
+        );
+    }
+
+    #[test]
+    fn solutions_iterator_ends_when_exhausted() {
+        let mut solutions = Solver::from_grid(grid(TWO_SOLUTIONS)).solutions();
+        let first = solutions.next().unwrap();
+        let second = solutions.next().unwrap();
+        assert_ne!(first, second);
+        assert!(solutions.next().is_none());
+        assert!(solutions.next().is_none());
+
+        assert_eq!(
+            Solver::from_grid(grid(PUZZLE))
+                .solutions()
+                .collect::<Vec<_>>(),
+            [grid(SOLUTION)]
+        );
+        assert_eq!(
+            Solver::from_grid(SudokuGrid::default())
+                .solutions()
+                .take(4)
+                .count(),
+            4
+        );
+    }
+
+    /// Apply the steps of a step solver to a puzzle, returning the grid and the last step
+    fn replay(puzzle: &SudokuGrid) -> (SudokuGrid, Option<SolveStep>) {
+        let mut replayed = puzzle.clone();
+        let mut last = None;
+        for step in StepSolver::new(puzzle.clone()) {
+            match step {
+                SolveStep::Place { row, col, value } => assert!(replayed.set(row, col, value)),
+                SolveStep::Remove { row, col } => replayed.unset(row, col),
+                SolveStep::Done | SolveStep::Failed => {}
+            }
+            last = Some(step);
+        }
+        (replayed, last)
+    }
+
+    #[test]
+    fn replaying_the_steps_solves_the_puzzle() {
+        let (replayed, last) = replay(&grid(HARD));
+        assert_eq!(last, Some(SolveStep::Done));
+        assert_eq!(Some(replayed), Solver::from_grid(grid(HARD)).solve());
+
+        let steps = StepSolver::new(grid(PUZZLE)).count();
+        assert_eq!(steps, grid(PUZZLE).empty_cells().count() + 1);
+    }
+
+    #[test]
+    fn replaying_an_unsolvable_puzzle_restores_it() {
+        // R1C8 and R1C9 both have to be 9, which is only found after placing one of them
+        let puzzle = SudokuGrid::from_triples(
+            (0..7)
+                .map(|col| (0, col, col as u8 + 1))
+                .chain([(4, 7, 8), (7, 8, 8)]),
+        )
+        .unwrap();
+        assert!(
+            StepSolver::new(puzzle.clone()).any(|step| matches!(step, SolveStep::Remove { .. }))
+        );
+        let (replayed, last) = replay(&puzzle);
+        assert_eq!(last, Some(SolveStep::Failed));
+        assert_eq!(replayed, puzzle);
+    }
+
+    #[test]
+    fn sandwich_clues_constrain_the_solution() {
+        let clue = |kind, index, sum| Constraint::Sandwich {
+            unit: (kind, index),
+            sum,
+        };
+        // The fourth row of the solution is 859761423
+        let solution = Solver::from_grid(grid(PUZZLE))
+            .with_constraint(clue(UnitKind::Row, 3, 13))
+            .solve();
+        assert_eq!(solution, Some(grid(SOLUTION)));
+        let solution = Solver::from_grid(grid(PUZZLE))
+            .with_constraint(clue(UnitKind::Row, 3, 14))
+            .solve();
+        assert_eq!(solution, None);
+
+        // Without R4C6 and R4C9, the fourth row can also be completed as 859763421
+        let ambiguous = grid(
+            "53467891267219534819834256785976.42.42685.79.713924856961537284287419635345286179",
+        );
+        let solution = Solver::from_grid(ambiguous.clone())
+            .with_constraint(clue(UnitKind::Row, 3, 22))
+            .solve()
+            .unwrap();
+        assert_eq!(solution.row_slice(3), &[8, 5, 9, 7, 6, 3, 4, 2, 1]);
+        let solution = Solver::from_grid(ambiguous.clone())
+            .with_constraint(clue(UnitKind::Col, 8, 15))
+            .solve();
+        assert_eq!(solution, Some(grid(SOLUTION)));
+        let solution = Solver::from_grid(ambiguous)
+            .with_constraint(clue(UnitKind::Col, 8, 13))
+            .solve();
+        assert_eq!(solution, None);
+    }
+
+    #[test]
+    fn proper_puzzles_have_one_solution() {
+        assert!(is_proper(&grid(PUZZLE)));
+        assert!(is_proper(&grid(SOLUTION)));
+        assert!(!is_proper(&grid(TWO_SOLUTIONS)));
+        assert!(!is_proper(&SudokuGrid::default()));
+    }
+
+    #[test]
+    fn minimal_puzzles_need_every_clue() {
+        // There are no proper puzzles with 16 clues, so every 17-clue puzzle is minimal
+        assert!(is_proper_and_minimal(&grid(HARD)));
+        assert!(!is_proper_and_minimal(&grid(SOLUTION)));
+        assert!(!is_proper_and_minimal(&grid(TWO_SOLUTIONS)));
+    }
+
+    #[test]
+    fn diagonal_puzzles_use_every_digit_on_the_diagonals() {
+        let puzzle = SudokuGrid::default()
+            .with_variant(Variant::Diagonal)
+            .unwrap();
+        let solution = Solver::from_grid(puzzle).solve().unwrap();
+        assert_eq!(solution.variant(), Variant::Diagonal);
+        assert!(solution.is_valid());
+        let main: u16 = (0..9)
+            .map(|k| 1 << (solution.at(k, k) - 1))
+            .fold(0, |a, b| a | b);
+        let anti: u16 = (0..9)
+            .map(|k| 1 << (solution.at(k, 8 - k) - 1))
+            .fold(0, |a, b| a | b);
+        assert_eq!((main, anti), (0x1ff, 0x1ff));
+    }
+
+    #[test]
+    fn diagonal_candidates_exclude_the_diagonals() {
+        let puzzle = SudokuGrid::from_triples([(0, 0, 5)])
+            .unwrap()
+            .with_variant(Variant::Diagonal)
+            .unwrap();
+        assert!(!puzzle.candidates(8, 8).any(|value| value == 5));
+        assert!(puzzle.candidates(8, 7).any(|value| value == 5));
+        assert!(SudokuGrid::from_triples([(0, 0, 5)])
+            .unwrap()
+            .candidates(8, 8)
+            .any(|value| value == 5));
+    }
+
+    #[test]
+    fn comparisons_constrain_the_solution() {
+        // R4C6 and R4C9 of the ambiguous grid hold 1 and 3 in some order
+        let ambiguous = grid(
+            "53467891267219534819834256785976.42.42685.79.713924856961537284287419635345286179",
+        );
+        let solution = Solver::from_grid(ambiguous.clone())
+            .with_constraint(Constraint::GreaterThan {
+                higher: (3, 5),
+                lower: (3, 8),
+            })
+            .solve()
+            .unwrap();
+        assert_eq!((solution.at(3, 5), solution.at(3, 8)), (3, 1));
+        let solution = Solver::from_grid(ambiguous)
+            .with_constraint(Constraint::GreaterThan {
+                higher: (3, 8),
+                lower: (3, 5),
+            })
+            .solve();
+        assert_eq!(solution, Some(grid(SOLUTION)));
+
+        let impossible = Solver::from_grid(grid(PUZZLE))
+            .with_constraint(Constraint::GreaterThan {
+                higher: (0, 1),
+                lower: (0, 0),
+            })
+            .solve();
+        assert_eq!(impossible, None);
+    }
+
+    #[test]
+    fn techniques_are_tried_from_the_easiest() {
+        let (solution, steps) = Solver::from_grid(grid(PUZZLE))
+            .solve_with_techniques()
+            .unwrap();
+        assert_eq!(solution, grid(SOLUTION));
+        assert!(steps
+            .iter()
+            .all(|step| step.technique == Technique::NakedSingle));
+        let placed: usize = steps.iter().map(|step| step.placements.len()).sum();
+        assert_eq!(placed, grid(PUZZLE).empty_cells().count());
+    }
+
+    #[test]
+    fn techniques_guess_only_when_stuck() {
+        let (solution, steps) = Solver::from_grid(grid(GUESSING))
+            .solve_with_techniques()
+            .unwrap();
+        assert_eq!(Some(solution), Solver::from_grid(grid(GUESSING)).solve());
+        let first_guess = steps
+            .iter()
+            .position(|step| step.technique == Technique::Guess)
+            .unwrap();
+        assert_eq!(steps[first_guess].placements.len(), 1);
+        // The same steps are taken until the techniques run out
+        let (stuck, logical) = stuck(GUESSING);
+        assert_eq!(logical, steps[..first_guess]);
+        assert!(stuck.empty_cells().count() > 0);
+
+        let mut stuck = grid(PUZZLE);
+        stuck.lock(0, 2);
+        assert!(Solver::from_grid(stuck).solve_with_techniques().is_none());
+    }
+
+    #[test]
+    fn wrong_guesses_are_taken_back() {
+        let (solution, steps) = Solver::from_grid(grid(AI_ESCARGOT))
+            .solve_with_techniques()
+            .unwrap();
+        assert_eq!(
+            Some(solution.clone()),
+            Solver::from_grid(grid(AI_ESCARGOT)).solve()
+        );
+        // Only the guesses that led to the solution are left, so replaying the steps solves it
+        let mut replayed = grid(AI_ESCARGOT);
+        for (row, col, value) in steps.iter().flat_map(|step| step.placements.clone()) {
+            assert!(replayed.set(row, col, value), "{}", cell_name(row, col));
+        }
+        assert_eq!(replayed, solution);
+        let rating = Solver::from_grid(grid(AI_ESCARGOT)).rate().unwrap();
+        assert!(rating.guesses > Solver::from_grid(grid(AI_ESCARGOT)).min_guesses());
+    }
+
+    #[test]
+    fn technique_searches_without_a_solution_give_none() {
+        // The techniques get stuck before they find the contradiction, so it takes guessing
+        assert!(stuck(WRONG_GUESS).0.empty_cells().count() > 0);
+        let solver = Solver::from_grid(grid(WRONG_GUESS));
+        assert_eq!(solver.rate(), None);
+        assert_eq!(solver.min_guesses(), 0);
+        assert_eq!(solver.first_guess_cell(), None);
+        assert!(solver.solve_with_techniques().is_none());
+    }
+
+    #[test]
+    fn techniques_used_lists_the_ladder() {
+        let (solution, techniques) = Solver::from_grid(grid(POINTING)).techniques_used();
+        assert!(solution.unwrap().is_solution_of(&grid(POINTING)));
+        assert_eq!(
+            techniques,
+            [
+                Technique::NakedSingle,
+                Technique::HiddenSingle,
+                Technique::PointingPair
+            ]
+        );
+
+        let (_, techniques) = Solver::from_grid(grid(PUZZLE)).techniques_used();
+        assert_eq!(techniques, [Technique::NakedSingle]);
+        let (_, techniques) = Solver::from_grid(grid(GUESSING)).techniques_used();
+        assert_eq!(techniques.last(), Some(&Technique::Guess));
+        assert_eq!(Solver::from_grid(grid(SOLUTION)).techniques_used().1, []);
+    }
+
+    #[test]
+    fn first_guess_cell_is_where_the_techniques_run_out() {
+        let solver = Solver::from_grid(grid(GUESSING));
+        let (row, col) = solver.first_guess_cell().unwrap();
+        let (stuck, _) = stuck(GUESSING);
+        assert_eq!(stuck.at(row, col), 0);
+        // The cell is not forced, otherwise a single would have placed it
+        assert!(stuck.candidates_mask(row, col).count_ones() >= 2);
+
+        assert_eq!(Solver::from_grid(grid(PUZZLE)).first_guess_cell(), None);
+        assert_eq!(Solver::from_grid(grid(POINTING)).first_guess_cell(), None);
+    }
+
+    #[test]
+    fn every_placed_cell_is_attributed() {
+        let (solution, placed_by) = Solver::from_grid(grid(GUESSING))
+            .solve_with_attribution()
+            .unwrap();
+        assert!(solution.is_solution_of(&grid(GUESSING)));
+        for (row, col, value) in grid(GUESSING).iter_cells() {
+            assert_eq!(
+                placed_by[row][col].is_none(),
+                value != 0,
+                "{}",
+                cell_name(row, col)
+            );
+        }
+        let guessed = placed_by
+            .iter()
+            .flatten()
+            .filter(|&&technique| technique == Some(Technique::Guess))
+            .count();
+        assert!(guessed > 0);
+
+        let (_, placed_by) = Solver::from_grid(grid(PUZZLE))
+            .solve_with_attribution()
+            .unwrap();
+        assert_eq!(placed_by[0][2], Some(Technique::NakedSingle));
+        assert_eq!(placed_by[0][0], None);
+    }
+
+    #[test]
+    fn logical_puzzles_need_no_guesses() {
+        assert_eq!(Solver::from_grid(grid(PUZZLE)).min_guesses(), 0);
+        assert_eq!(Solver::from_grid(grid(POINTING)).min_guesses(), 0);
+        assert_eq!(Solver::from_grid(grid(SOLUTION)).min_guesses(), 0);
+        let guesses = Solver::from_grid(grid(GUESSING)).min_guesses();
+        assert!(guesses > 0);
+        assert!(
+            guesses as u64
+                <= Solver::from_grid(grid(GUESSING))
+                    .solve_with_stats()
+                    .1
+                    .guesses
+        );
+    }
+
+    #[test]
+    fn every_cell_order_finds_the_solution() {
+        for cell_order in [
+            CellOrder::RowMajor,
+            CellOrder::Mrv,
+            CellOrder::MrvRandomTiebreak,
+            CellOrder::MostConstrainingVariable,
+        ] {
+            for propagate in [false, true] {
+                let config = SolverConfig {
+                    cell_order,
+                    propagate,
+                    seed: 7,
+                };
+                for (puzzle, solution) in [
+                    (PUZZLE, grid(SOLUTION)),
+                    (GUESSING, Solver::from_grid(grid(GUESSING)).solve().unwrap()),
+                ] {
+                    let found = Solver::from_grid(grid(puzzle)).with_config(config).solve();
+                    assert_eq!(found.as_ref(), Some(&solution), "{:?}", config);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plain_backtracking_guesses_more() {
+        let config = SolverConfig {
+            cell_order: CellOrder::RowMajor,
+            propagate: false,
+            seed: 0,
+        };
+        let (_, plain) = Solver::from_grid(grid(PUZZLE))
+            .with_config(config)
+            .solve_with_stats();
+        let (_, default) = Solver::from_grid(grid(PUZZLE)).solve_with_stats();
+        assert!(plain.guesses > default.guesses);
+        assert_eq!(
+            Solver::from_grid(grid(PUZZLE))
+                .with_config(SolverConfig::default())
+                .solve_with_stats()
+                .1,
+            default
+        );
+    }
+
+    #[test]
+    fn uniqueness_checks_give_up_at_the_guess_limit() {
+        assert_eq!(
+            Solver::from_grid(grid(GUESSING)).has_unique_solution_within(100_000),
+            Some(true)
+        );
+        assert_eq!(
+            Solver::from_grid(grid(TWO_SOLUTIONS)).has_unique_solution_within(100),
+            Some(false)
+        );
+        assert_eq!(
+            Solver::from_grid(grid(GUESSING)).has_unique_solution_within(1),
+            None
+        );
+        assert_eq!(
+            Solver::from_grid(SudokuGrid::default()).has_unique_solution_within(0),
+            None
+        );
+    }
+
+    #[test]
+    fn ratings_order_puzzles_by_difficulty() {
+        let rate = |s| Solver::from_grid(grid(s)).rate().unwrap();
+        assert_eq!(
+            rate(SOLUTION),
+            Rating {
+                hardest: None,
+                guesses: 0
+            }
+        );
+        assert_eq!(rate(PUZZLE).hardest, Some(Technique::NakedSingle));
+        assert!(rate(PUZZLE) < rate(POINTING));
+        assert!(rate(POINTING) < rate(GUESSING));
+        assert!(rate(GUESSING) < rate(INKALA_2012));
+        for puzzle in [AI_ESCARGOT, INKALA_2012, EASTER_MONSTER] {
+            assert_eq!(rate(puzzle).hardest, Some(Technique::Guess));
+            assert!(rate(puzzle).guesses >= Solver::from_grid(grid(puzzle)).min_guesses());
+        }
+    }
+
+    #[test]
+    fn difficulty_tiers_follow_the_ratings() {
+        let difficulty = |s| Solver::from_grid(grid(s)).rate().unwrap().difficulty();
+        assert_eq!(difficulty(SOLUTION), Difficulty::Easy);
+        assert_eq!(difficulty(PUZZLE), Difficulty::Easy);
+        assert_eq!(difficulty(POINTING), Difficulty::Medium);
+        assert_eq!(difficulty(GUESSING), Difficulty::Hard);
+        // Puzzles published as the hardest ones are at the top tier
+        assert_eq!(difficulty(AI_ESCARGOT), Difficulty::Expert);
+        assert_eq!(difficulty(INKALA_2012), Difficulty::Expert);
+        assert_eq!(difficulty(EASTER_MONSTER), Difficulty::Expert);
+        let rating = |hardest, guesses| Rating {
+            hardest: Some(hardest),
+            guesses,
+        };
+        assert_eq!(rating(Technique::XWing, 0).difficulty(), Difficulty::Hard);
+        assert_eq!(rating(Technique::Guess, 1).difficulty(), Difficulty::Hard);
+        assert_eq!(rating(Technique::Guess, 2).difficulty(), Difficulty::Expert);
+        assert!(Difficulty::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn timed_out_searches_return_the_best_partial_grid() {
+        let puzzle = grid(GUESSING);
+        let mut solver = Solver::from_grid(puzzle.clone());
+        solver.guess_limit = Some(3);
+        let future = Instant::now() + std::time::Duration::from_secs(60);
+        let partial = solver.solve_until_partial(future).unwrap_err();
+        assert!(partial.timed_out);
+        assert!(partial.best.empty_cells().count() < puzzle.empty_cells().count());
+        assert!(puzzle
+            .iter_cells()
+            .all(|(row, col, value)| value == 0 || partial.best.at(row, col) == value));
+        assert!(partial.best.is_valid());
+
+        let solution = Solver::from_grid(grid(PUZZLE)).solve_until_partial(future);
+        assert_eq!(solution, Ok(grid(SOLUTION)));
+        let mut stuck = grid(PUZZLE);
+        stuck.lock(0, 2);
+        let partial = Solver::from_grid(stuck)
+            .solve_until_partial(future)
+            .unwrap_err();
+        assert!(!partial.timed_out);
+    }
+
+    /// Remove the clues of a solution in row-major order while the puzzle stays unique,
+    /// until `clues` clues are left
+    fn minimize(solution: &SudokuGrid, clues: usize) -> SudokuGrid {
+        let mut puzzle = solution.clone();
+        for (row, col) in (0..81).map(|k| (k / 9, k % 9)) {
+            if puzzle.empty_cells().count() + clues >= 81 {
+                break;
+            }
+            let value = puzzle.at(row, col);
+            puzzle.unset(row, col);
+            if !Solver::from_grid(puzzle.clone()).has_unique_solution() {
+                assert!(puzzle.set(row, col, value));
+            }
+        }
+        puzzle
+    }
+
+    #[test]
+    fn disjoint_groups_puzzles_are_solved() {
+        let empty = SudokuGrid::default()
+            .with_variant(Variant::DisjointGroups)
+            .unwrap();
+        let full = Solver::from_grid(empty).solve().unwrap();
+        let puzzle = minimize(&full, 0);
+        assert!(puzzle.empty_cells().count() > 0);
+        assert_eq!(Solver::from_grid(puzzle.clone()).solve(), Some(full));
+
+        // The same clues are not enough without the disjoint groups
+        let classic = puzzle.with_variant(Variant::Classic).unwrap();
+        assert_eq!(Solver::from_grid(classic).count_solutions(2), 2);
+    }
+
+    #[test]
+    fn disjoint_groups_candidates_exclude_the_group() {
+        let puzzle = SudokuGrid::from_triples([(0, 0, 5)])
+            .unwrap()
+            .with_variant(Variant::DisjointGroups)
+            .unwrap();
+        assert!(!puzzle.candidates(3, 6).any(|value| value == 5));
+        assert!(puzzle.candidates(3, 7).any(|value| value == 5));
+        let mut grid = puzzle;
+        assert!(!grid.set(6, 6, 5));
+        assert!(grid.set(7, 7, 5));
+    }
+
+    #[test]
+    fn nonconsecutive_puzzles_are_solved() {
+        let empty = SudokuGrid::default()
+            .with_variant(Variant::NonConsecutive)
+            .unwrap();
+        let full = Solver::from_grid(empty).solve().unwrap();
+        assert!(full.is_valid());
+        // Removing every clue that can go takes too long on sparse nonconsecutive grids
+        let puzzle = minimize(&full, 30);
+        assert_eq!(puzzle.empty_cells().count(), 51);
+        assert_eq!(Solver::from_grid(puzzle.clone()).solve(), Some(full));
+        let classic = puzzle.with_variant(Variant::Classic).unwrap();
+        assert_eq!(Solver::from_grid(classic).count_solutions(2), 2);
+    }
+
+    #[test]
+    fn variants_can_be_combined() {
+        let variants = Variant::Diagonal | Variant::NonConsecutive;
+        let empty = SudokuGrid::default().with_variant(variants).unwrap();
+        let solution = Solver::from_grid(empty).solve().unwrap();
+        assert_eq!(solution.variant(), variants);
+        assert!(solution.is_valid());
+        assert!(solution.with_variant(Variant::Diagonal).is_ok());
+        assert!(solution.with_variant(Variant::NonConsecutive).is_ok());
+    }
+
+    #[test]
+    fn thermos_increase_from_the_bulb() {
+        // A thermometer along the whole first row leaves a single way to fill it
+        let row = Constraint::Thermo((0..9).map(|col| (0, col)).collect());
+        let solution = Solver::from_grid(SudokuGrid::default())
+            .with_constraint(row)
+            .solve()
+            .unwrap();
+        assert_eq!(solution.row_slice(0), &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+        // A short thermometer picks one of the two solutions of the ambiguous grid
+        let ambiguous = grid(
+            "53467891267219534819834256785976.42.42685.79.713924856961537284287419635345286179",
+        );
+        let solve = |path: Vec<(usize, usize)>| {
+            Solver::from_grid(ambiguous.clone())
+                .with_constraint(Constraint::Thermo(path))
+                .solve()
+        };
+        assert_eq!(solve(vec![(3, 5), (3, 8)]), Some(grid(SOLUTION)));
+        assert_eq!(solve(vec![(3, 8), (3, 5)]).map(|g| g.at(3, 5)), Some(3));
+        // R4C7 holds 4, so R4C6 cannot be below it and R4C9 above it at the same time
+        assert_eq!(solve(vec![(3, 8), (3, 6), (3, 5)]), None);
+    }
+
+    #[test]
+    fn thermos_need_room_for_every_cell() {
+        let thermo = Constraint::Thermo;
+        // A 9 in the middle of a thermometer leaves no room for the cells after it
+        let grid = SudokuGrid::from_triples([(4, 4, 9)]).unwrap();
+        assert!(!thermo(vec![(4, 3), (4, 4), (4, 5)]).allows(&grid));
+        assert!(thermo(vec![(4, 2), (4, 3), (4, 4)]).allows(&grid));
+        // A 2 three cells after a 1 leaves no digit between them
+        let grid = SudokuGrid::from_triples([(0, 0, 1), (3, 0, 2)]).unwrap();
+        assert!(!thermo((0..4).map(|row| (row, 0)).collect()).allows(&grid));
+        let long = thermo((0..9).map(|row| (row, 0)).chain([(0, 1)]).collect());
+        assert!(!long.allows(&SudokuGrid::default()));
+    }
+
+    #[test]
+    fn equal_cells_clone_their_digits() {
+        let ambiguous = grid(
+            "53467891267219534819834256785976.42.42685.79.713924856961537284287419635345286179",
+        );
+        let solve = |groups: Vec<Vec<(usize, usize)>>| {
+            Solver::from_grid(ambiguous.clone())
+                .with_constraint(Constraint::EqualCells(groups))
+                .solve()
+        };
+        // R1C2 holds 3, so R4C6 has to as well
+        assert_eq!(
+            solve(vec![vec![(3, 5), (0, 1)]]).map(|g| g.at(3, 5)),
+            Some(3)
+        );
+        // R1C1 holds 5, which R4C6 cannot take
+        assert_eq!(solve(vec![vec![(3, 5), (0, 0)]]), None);
+
+        // The first row cloned into the second one is never a valid square
+        let clone = Constraint::EqualCells((0..9).map(|col| vec![(0, col), (1, col)]).collect());
+        let solver = Solver::from_grid(SudokuGrid::default()).with_constraint(clone);
+        assert_eq!(solver.solve(), None);
+    }
+
+    #[test]
+    fn equal_cells_clone_a_region() {
+        // The top left square cloned into the middle square, with a few clues in each
+        let first: Vec<(usize, usize)> = (0..9).map(|k| (k / 3, k % 3)).collect();
+        let clone = Constraint::EqualCells(
+            first
+                .iter()
+                .map(|&(row, col)| vec![(row, col), (row + 3, col + 3)])
+                .collect(),
+        );
+        let grid = SudokuGrid::from_triples([(0, 0, 1), (0, 1, 2), (4, 5, 3)]).unwrap();
+        let solution = Solver::from_grid(grid)
+            .with_constraint(clone.clone())
+            .solve()
+            .unwrap();
+        assert!(solution.is_valid() && clone.allows(&solution));
+        assert_eq!(solution.at(3, 3), 1);
+        assert_eq!(solution.at(3, 4), 2);
+        assert_eq!(solution.at(1, 2), 3);
+    }
+
+    #[test]
+    fn boxes_redundant_flags_degenerate_puzzles() {
+        // With one cell missing from each row, the rows alone give the missing digits
+        let solution = grid(SOLUTION);
+        let mut puzzle = solution.clone();
+        for k in 0..9 {
+            puzzle.unset(k, (k * 4) % 9);
+        }
+        assert!(Solver::from_grid(puzzle.clone()).boxes_redundant());
+        assert!(!Solver::from_grid(grid(PUZZLE)).boxes_redundant());
+        assert!(!Solver::from_grid(SudokuGrid::default()).boxes_redundant());
+
+        // R1C3 and R4C7 hold 4 while R1C7 and R4C3 hold 9, so the rows and columns allow
+        // the two digits to be swapped, but the squares do not
+        let mut puzzle = solution;
+        for (row, col) in [(0, 2), (0, 6), (3, 2), (3, 6)] {
+            puzzle.unset(row, col);
+        }
+        assert!(Solver::from_grid(puzzle.clone()).has_unique_solution());
+        assert!(!Solver::from_grid(puzzle).boxes_redundant());
+    }
 }
@@ -0,0 +1,326 @@
+use crate::input::{unit_cells, SudokuGrid, SQUARE_OF};
+
+/// The candidates of every cell of a grid, as bitmasks where bit `d - 1` is set if
+/// digit `d` may still be placed in the cell.
+///
+/// Candidates start out as `SudokuGrid::candidates_mask` and can be narrowed down
+/// further by strategies that eliminate digits the grid itself does not rule out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateGrid {
+    masks: [[u16; 9]; 9],
+}
+
+impl CandidateGrid {
+    /// Create the candidates of a grid, with no eliminations.
+    pub fn from_grid(grid: &SudokuGrid) -> Self {
+        Self {
+            masks: std::array::from_fn(|row| {
+                std::array::from_fn(|col| grid.candidates_mask(row, col))
+            }),
+        }
+    }
+
+    /// Get the candidates of a cell as a bitmask. Returns 0 for a filled cell.
+    pub fn mask(&self, row: usize, col: usize) -> u16 {
+        self.masks[row][col]
+    }
+
+    /// Check if a digit is a candidate of a cell.
+    pub fn contains(&self, row: usize, col: usize, value: u8) -> bool {
+        self.masks[row][col] & (1 << (value - 1)) != 0
+    }
+
+    /// Eliminate a digit from the candidates of a cell.
+    /// Returns true if the digit was a candidate.
+    pub fn remove(&mut self, row: usize, col: usize, value: u8) -> bool {
+        let contained = self.contains(row, col, value);
+        self.masks[row][col] &= !(1 << (value - 1));
+        contained
+    }
+
+    /// Set a value in the grid and update the candidates to match, keeping
+    /// the eliminations made so far.
+    /// Returns false if the value cannot be set, in which case nothing changes.
+    pub fn place(&mut self, grid: &mut SudokuGrid, row: usize, col: usize, value: u8) -> bool {
+        if !grid.set(row, col, value) {
+            return false;
+        }
+        for (i, masks) in self.masks.iter_mut().enumerate() {
+            for (j, mask) in masks.iter_mut().enumerate() {
+                *mask &= grid.candidates_mask(i, j);
+            }
+        }
+        true
+    }
+}
+
+/// A solving technique that can be plugged into the solver with `Solver::with_strategy`.
+///
+/// The solver applies its strategies after filling in the cells that have a single
+/// candidate, until none of them makes progress.
+pub trait Strategy {
+    /// Apply the strategy once, placing values with `CandidateGrid::place` and
+    /// eliminating candidates with `CandidateGrid::remove`.
+    /// Returns true if anything was placed or eliminated.
+    fn apply(&self, grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool;
+}
+
+/// Place every cell that has a single candidate left.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NakedSingles;
+
+impl Strategy for NakedSingles {
+    fn apply(&self, grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool {
+        let mut progress = false;
+        for row in 0..9 {
+            for col in 0..9 {
+                let mask = cands.mask(row, col);
+                if mask.count_ones() == 1 {
+                    let value = mask.trailing_zeros() as u8 + 1;
+                    progress |= cands.place(grid, row, col, value);
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// Place every digit that has a single possible cell left in a row, column, or square.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HiddenSingles;
+
+impl Strategy for HiddenSingles {
+    fn apply(&self, grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool {
+        let mut progress = false;
+        for unit in 0..27 {
+            for value in 1..=9u8 {
+                let mut places = unit_cells(unit).filter(|&(i, j)| cands.contains(i, j, value));
+                if let (Some((i, j)), None) = (places.next(), places.next()) {
+                    progress |= cands.place(grid, i, j, value);
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// Eliminate the two candidates of a pair of cells from the rest of a row, column, or
+/// square when both cells have the same two candidates and nothing else.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NakedPairs;
+
+impl Strategy for NakedPairs {
+    fn apply(&self, _grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool {
+        let mut progress = false;
+        for unit in 0..27 {
+            let cells: Vec<(usize, usize)> = unit_cells(unit).collect();
+            for (k, &(i1, j1)) in cells.iter().enumerate() {
+                let mask = cands.mask(i1, j1);
+                if mask.count_ones() != 2 {
+                    continue;
+                }
+                let Some(&(i2, j2)) = cells[k + 1..]
+                    .iter()
+                    .find(|&&(i, j)| cands.mask(i, j) == mask)
+                else {
+                    continue;
+                };
+                for &(i, j) in cells.iter() {
+                    if (i, j) != (i1, j1) && (i, j) != (i2, j2) {
+                        progress |= remove_mask(cands, i, j, mask);
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// Eliminate a digit from the rest of a row or column when, within a square,
+/// the digit can only go in that row or column.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PointingPairs;
+
+impl Strategy for PointingPairs {
+    fn apply(&self, _grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool {
+        let mut progress = false;
+        for square in 0..9 {
+            for value in 1..=9u8 {
+                let places: Vec<(usize, usize)> = unit_cells(18 + square)
+                    .filter(|&(i, j)| cands.contains(i, j, value))
+                    .collect();
+                let Some(&(row, col)) = places.first() else {
+                    continue;
+                };
+                if places.iter().all(|&(i, _)| i == row) {
+                    for j in (0..9).filter(|j| j / 3 != col / 3) {
+                        progress |= cands.remove(row, j, value);
+                    }
+                }
+                if places.iter().all(|&(_, j)| j == col) {
+                    for i in (0..9).filter(|i| i / 3 != row / 3) {
+                        progress |= cands.remove(i, col, value);
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// Eliminate a digit from the rest of a square when, within a row or column,
+/// the digit can only go in that square.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BoxLineReduction;
+
+impl Strategy for BoxLineReduction {
+    fn apply(&self, _grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool {
+        let mut progress = false;
+        for line in 0..18 {
+            for value in 1..=9u8 {
+                let places: Vec<(usize, usize)> = unit_cells(line)
+                    .filter(|&(i, j)| cands.contains(i, j, value))
+                    .collect();
+                let Some(&(row, col)) = places.first() else {
+                    continue;
+                };
+                let square = SQUARE_OF[row][col];
+                if places.iter().any(|&(i, j)| SQUARE_OF[i][j] != square) {
+                    continue;
+                }
+                for (i, j) in unit_cells(18 + square) {
+                    if !unit_cells(line).any(|cell| cell == (i, j)) {
+                        progress |= cands.remove(i, j, value);
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// Eliminate a digit from two columns when, in two rows, the digit can only go
+/// in those same two columns, and the same with rows and columns swapped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XWing;
+
+impl Strategy for XWing {
+    fn apply(&self, _grid: &mut SudokuGrid, cands: &mut CandidateGrid) -> bool {
+        let mut progress = false;
+        for value in 1..=9u8 {
+            for transposed in [false, true] {
+                // Look at rows, or at columns when transposed
+                let cell = |line: usize, k: usize| if transposed { (k, line) } else { (line, k) };
+                // The positions along each line where the digit can go, as a bitmask
+                let places: [u16; 9] = std::array::from_fn(|line| {
+                    (0..9)
+                        .filter(|&k| {
+                            let (i, j) = cell(line, k);
+                            cands.contains(i, j, value)
+                        })
+                        .fold(0, |mask, k| mask | 1 << k)
+                });
+                for a in 0..9 {
+                    let pair = places[a];
+                    if pair.count_ones() != 2 {
+                        continue;
+                    }
+                    let Some(b) = (a + 1..9).find(|&b| places[b] == pair) else {
+                        continue;
+                    };
+                    for line in (0..9).filter(|&line| line != a && line != b) {
+                        for k in (0..9).filter(|k| pair & (1 << k) != 0) {
+                            let (i, j) = cell(line, k);
+                            progress |= cands.remove(i, j, value);
+                        }
+                    }
+                }
+            }
+        }
+        progress
+    }
+}
+
+/// Eliminate every digit of a bitmask from the candidates of a cell.
+/// Returns true if any of the digits was a candidate.
+fn remove_mask(cands: &mut CandidateGrid, row: usize, col: usize, mask: u16) -> bool {
+    let mut removed = false;
+    for value in 1..=9u8 {
+        if mask & (1 << (value - 1)) != 0 {
+            removed |= cands.remove(row, col, value);
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The candidates of an empty grid with a digit ruled out of some cells
+    fn without(value: u8, cells: impl IntoIterator<Item = (usize, usize)>) -> CandidateGrid {
+        let mut cands = CandidateGrid::from_grid(&SudokuGrid::default());
+        for (row, col) in cells {
+            cands.remove(row, col, value);
+        }
+        cands
+    }
+
+    #[test]
+    fn naked_pairs_clear_their_digits_from_the_unit() {
+        let mut cands = CandidateGrid::from_grid(&SudokuGrid::default());
+        for col in [0, 1] {
+            for value in 1..=7 {
+                cands.remove(0, col, value);
+            }
+        }
+        let mut grid = SudokuGrid::default();
+        assert!(NakedPairs.apply(&mut grid, &mut cands));
+        assert_eq!(cands.mask(0, 0), 0b1_1000_0000);
+        assert_eq!(cands.mask(0, 5), 0b0_0111_1111);
+        assert_eq!(cands.mask(2, 2), 0b0_0111_1111);
+        assert!(!NakedPairs.apply(&mut grid, &mut cands));
+    }
+
+    #[test]
+    fn pointing_pairs_clear_the_rest_of_the_row() {
+        // In the first square, 1 can only go in the first row
+        let mut cands = without(1, (1..3).flat_map(|i| (0..3).map(move |j| (i, j))));
+        assert!(PointingPairs.apply(&mut SudokuGrid::default(), &mut cands));
+        assert!(cands.contains(0, 2, 1));
+        assert!((3..9).all(|j| !cands.contains(0, j, 1)));
+        assert!(cands.contains(1, 3, 1));
+    }
+
+    #[test]
+    fn box_line_reduction_clears_the_rest_of_the_square() {
+        // In the first row, 1 can only go in the first square
+        let mut cands = without(1, (3..9).map(|j| (0, j)));
+        assert!(BoxLineReduction.apply(&mut SudokuGrid::default(), &mut cands));
+        assert!(cands.contains(0, 0, 1));
+        assert!((1..3).all(|i| (0..3).all(|j| !cands.contains(i, j, 1))));
+        assert!(cands.contains(1, 3, 1));
+    }
+
+    #[test]
+    fn x_wings_clear_the_two_columns() {
+        // In the first and fifth rows, 1 can only go in the first and fifth columns
+        let others = [0, 4]
+            .into_iter()
+            .flat_map(|i| [1, 2, 3, 5, 6, 7, 8].map(|j| (i, j)));
+        let mut cands = without(1, others);
+        assert!(XWing.apply(&mut SudokuGrid::default(), &mut cands));
+        assert!(cands.contains(0, 0, 1) && cands.contains(4, 4, 1));
+        assert!(!cands.contains(2, 0, 1) && !cands.contains(8, 4, 1));
+        assert!(cands.contains(2, 1, 1));
+    }
+
+    #[test]
+    fn singles_place_values() {
+        let mut grid = SudokuGrid::default();
+        let mut cands = without(1, (1..9).map(|j| (0, j)));
+        assert!(HiddenSingles.apply(&mut grid, &mut cands));
+        assert_eq!(grid.at(0, 0), 1);
+        assert!(!cands.contains(5, 0, 1));
+    }
+}
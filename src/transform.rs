@@ -0,0 +1,280 @@
+use crate::input::{SudokuGrid, Variant, Variants};
+
+/// A symmetry of the Sudoku grid.
+///
+/// Every transform maps rows, columns, and squares onto rows, columns, and squares,
+/// so a valid grid stays valid and a puzzle keeps the same number of solutions.
+/// The rotations, flips, transposes, and relabelings also map the two main diagonals
+/// onto each other, so the transformed grid keeps the variant of the original.
+/// Band and stack swaps do not, and they can separate adjacent cells, so they drop
+/// `Variant::Diagonal` and `Variant::NonConsecutive`, as does relabeling for
+/// `Variant::NonConsecutive` unless it reverses the digits.
+/// Every transform keeps the position of a cell within its square lined up with the
+/// other squares, so `Variant::DisjointGroups` is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Rotate clockwise by 90 degrees
+    Rotate90,
+    /// Rotate by 180 degrees
+    Rotate180,
+    /// Rotate clockwise by 270 degrees
+    Rotate270,
+    /// Mirror the columns, swapping left and right
+    FlipHorizontal,
+    /// Mirror the rows, swapping top and bottom
+    FlipVertical,
+    /// Reflect across the main diagonal, swapping rows and columns
+    Transpose,
+    /// Reflect across the anti-diagonal
+    AntiTranspose,
+    /// Swap two bands of 3 rows, given by their 0-based indices
+    SwapBands(usize, usize),
+    /// Swap two stacks of 3 columns, given by their 0-based indices
+    SwapStacks(usize, usize),
+    /// Replace each digit `d` with `labels[d - 1]`.
+    /// The labels must be a permutation of the digits 1 to 9.
+    Relabel([u8; 9]),
+}
+
+impl Transform {
+    /// Get the position a cell moves to under the transform.
+    pub(crate) fn map(self, row: usize, col: usize) -> (usize, usize) {
+        match self {
+            Transform::Rotate90 => (col, 8 - row),
+            Transform::Rotate180 => (8 - row, 8 - col),
+            Transform::Rotate270 => (8 - col, row),
+            Transform::FlipHorizontal => (row, 8 - col),
+            Transform::FlipVertical => (8 - row, col),
+            Transform::Transpose => (col, row),
+            Transform::AntiTranspose => (8 - col, 8 - row),
+            Transform::SwapBands(a, b) => (swap_group(row, a, b), col),
+            Transform::SwapStacks(a, b) => (row, swap_group(col, a, b)),
+            Transform::Relabel(_) => (row, col),
+        }
+    }
+
+    /// Get the value a digit is replaced with under the transform.
+    fn label(self, value: u8) -> u8 {
+        match self {
+            Transform::Relabel(labels) if value != 0 => labels[value as usize - 1],
+            _ => value,
+        }
+    }
+
+    /// Check if the transform maps the rules added by a variant onto themselves.
+    fn keeps_variant(self, variant: Variant) -> bool {
+        match (variant, self) {
+            (Variant::Diagonal | Variant::NonConsecutive, Transform::SwapBands(..))
+            | (Variant::Diagonal | Variant::NonConsecutive, Transform::SwapStacks(..)) => false,
+            // Only the identity and reversing the digits keep consecutive digits consecutive
+            (Variant::NonConsecutive, Transform::Relabel(labels)) => {
+                labels.windows(2).all(|pair| pair[0].abs_diff(pair[1]) == 1)
+            }
+            _ => true,
+        }
+    }
+
+    /// Get the transform that undoes this one.
+    pub fn inverse(self) -> Transform {
+        match self {
+            Transform::Rotate90 => Transform::Rotate270,
+            Transform::Rotate270 => Transform::Rotate90,
+            Transform::Relabel(labels) => {
+                let mut inverse = [0; 9];
+                for (digit, &label) in (1..=9).zip(labels.iter()) {
+                    inverse[label as usize - 1] = digit;
+                }
+                Transform::Relabel(inverse)
+            }
+            t => t,
+        }
+    }
+
+    /// Apply the transform to a grid, returning the transformed grid.
+    ///
+    /// Panics if a band or stack index is not less than 3, or the labels of
+    /// `Transform::Relabel` are not a permutation of the digits 1 to 9.
+    pub fn apply(self, grid: &SudokuGrid) -> SudokuGrid {
+        if let Transform::Relabel(labels) = self {
+            let mut sorted = labels;
+            sorted.sort_unstable();
+            assert_eq!(
+                sorted,
+                [1, 2, 3, 4, 5, 6, 7, 8, 9],
+                "labels must be a permutation of the digits 1 to 9"
+            );
+        }
+        let triples = (0..81).map(|k| {
+            let (row, col) = self.map(k / 9, k % 9);
+            (row, col, self.label(grid.at(k / 9, k % 9)))
+        });
+        // Safe to unwrap because the transform maps units onto units
+        let transformed = SudokuGrid::from_triples(triples).unwrap();
+        let kept: Variants = grid
+            .variant()
+            .iter()
+            .filter(|&variant| self.keeps_variant(variant))
+            .collect();
+        // Safe to unwrap because the rules of the kept variants are mapped onto themselves
+        transformed.with_variant(kept).unwrap()
+    }
+}
+
+/// Move an index from group `a` of 3 indices to group `b`, and vice versa.
+fn swap_group(index: usize, a: usize, b: usize) -> usize {
+    assert!(a < 3 && b < 3, "band and stack indices must be less than 3");
+    match index / 3 {
+        group if group == a => b * 3 + index % 3,
+        group if group == b => a * 3 + index % 3,
+        _ => index,
+    }
+}
+
+/// Apply the same transform to a puzzle and its solution, so that the transformed
+/// puzzle has a different clue pattern but solves to the transformed solution.
+pub fn transform_pair(
+    puzzle: &SudokuGrid,
+    solution: &SudokuGrid,
+    t: Transform,
+) -> (SudokuGrid, SudokuGrid) {
+    (t.apply(puzzle), t.apply(solution))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    /// The puzzle from the Wikipedia article on Sudoku
+    const PUZZLE: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    /// The solution of `PUZZLE`
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    const SYMMETRIES: [Transform; 7] = [
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipHorizontal,
+        Transform::FlipVertical,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    fn grid(puzzle: &str) -> SudokuGrid {
+        puzzle.parse().unwrap()
+    }
+
+    #[test]
+    fn transformed_puzzles_solve_to_the_transformed_solution() {
+        for t in SYMMETRIES {
+            let (puzzle, solution) = transform_pair(&grid(PUZZLE), &grid(SOLUTION), t);
+            assert_ne!(puzzle, grid(PUZZLE), "{:?}", t);
+            assert_eq!(Solver::from_grid(puzzle).solve(), Some(solution), "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn inverse_transforms_undo_the_transform() {
+        for t in SYMMETRIES {
+            assert_eq!(
+                t.inverse().apply(&t.apply(&grid(PUZZLE))),
+                grid(PUZZLE),
+                "{:?}",
+                t
+            );
+        }
+        let rotated = Transform::Rotate90.apply(&grid(PUZZLE));
+        assert_eq!(rotated.at(0, 8), 5);
+        assert_eq!(rotated.at(0, 0), 0);
+        assert_eq!(
+            Transform::Rotate90.apply(&Transform::Rotate90.apply(&grid(PUZZLE))),
+            Transform::Rotate180.apply(&grid(PUZZLE))
+        );
+    }
+
+    #[test]
+    fn band_and_stack_swaps_move_whole_groups() {
+        let puzzle = grid(PUZZLE);
+        let swapped = puzzle.apply_transform(Transform::SwapBands(0, 2));
+        assert_eq!(swapped.row_slice(0), puzzle.row_slice(6));
+        assert_eq!(swapped.row_slice(8), puzzle.row_slice(2));
+        assert_eq!(swapped.row_slice(4), puzzle.row_slice(4));
+        let swapped = puzzle.apply_transform(Transform::SwapStacks(0, 1));
+        assert_eq!(swapped.col_values(3), puzzle.col_values(0));
+        assert_eq!(swapped.col_values(8), puzzle.col_values(8));
+        assert_eq!(
+            Solver::from_grid(swapped).solve(),
+            Some(grid(SOLUTION).apply_transform(Transform::SwapStacks(0, 1)))
+        );
+    }
+
+    #[test]
+    fn relabeling_replaces_the_digits() {
+        let labels = [9, 8, 7, 6, 5, 4, 3, 2, 1];
+        let relabeled = grid(PUZZLE).apply_transform(Transform::Relabel(labels));
+        assert_eq!(relabeled.row_slice(0), &[5, 7, 0, 0, 3, 0, 0, 0, 0]);
+        let inverse = Transform::Relabel(labels).inverse();
+        assert_eq!(relabeled.apply_transform(inverse), grid(PUZZLE));
+    }
+
+    #[test]
+    #[should_panic(expected = "labels must be a permutation of the digits 1 to 9")]
+    fn relabeling_requires_a_permutation() {
+        grid(PUZZLE).apply_transform(Transform::Relabel([1; 9]));
+    }
+
+    #[test]
+    fn swaps_give_a_classic_grid() {
+        let diagonal = grid(PUZZLE).with_variant(Variant::Diagonal).unwrap();
+        assert_eq!(
+            diagonal.apply_transform(Transform::Rotate90).variant(),
+            Variant::Diagonal
+        );
+        assert_eq!(
+            diagonal
+                .apply_transform(Transform::SwapBands(0, 1))
+                .variant(),
+            Variant::Classic
+        );
+    }
+
+    #[test]
+    fn disjoint_groups_survive_every_transform() {
+        let empty = SudokuGrid::default()
+            .with_variant(Variant::DisjointGroups)
+            .unwrap();
+        let solution = Solver::from_grid(empty).solve().unwrap();
+        for t in [
+            Transform::Rotate90,
+            Transform::AntiTranspose,
+            Transform::SwapBands(0, 2),
+            Transform::SwapStacks(1, 2),
+        ] {
+            let transformed = solution.apply_transform(t);
+            assert_eq!(transformed.variant(), Variant::DisjointGroups);
+            assert!(transformed.is_valid());
+        }
+    }
+
+    #[test]
+    fn nonconsecutive_grids_keep_adjacency() {
+        let empty = SudokuGrid::default()
+            .with_variant(Variant::NonConsecutive | Variant::DisjointGroups)
+            .unwrap();
+        let solution = Solver::from_grid(empty).solve().unwrap();
+        let variant = |t: Transform| solution.apply_transform(t).variant();
+        assert_eq!(variant(Transform::Rotate90), solution.variant());
+        assert_eq!(
+            variant(Transform::Relabel([9, 8, 7, 6, 5, 4, 3, 2, 1])),
+            solution.variant()
+        );
+        assert_eq!(
+            variant(Transform::Relabel([2, 1, 3, 4, 5, 6, 7, 8, 9])),
+            Variant::DisjointGroups
+        );
+        assert_eq!(variant(Transform::SwapBands(0, 1)), Variant::DisjointGroups);
+    }
+}
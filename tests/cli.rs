@@ -0,0 +1,428 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use sudoku_rs::{
+    input::{SudokuGrid, Variant},
+    solver::Solver,
+};
+
+/// Run the command line tool with the given arguments, writing `stdin` to its standard input.
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sudoku-rs"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Write a file for a test to the temporary directory, returning its path.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("sudoku-rs-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn invalid_puzzles_are_reported_before_solving() {
+    let puzzle =
+        "55..7....5..19.....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+    let output = run(&[], puzzle);
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Puzzle is invalid:\n  \
+         R1C1 and R1C2 both contain 5\n  \
+         R1C1 and R2C1 both contain 5\n  \
+         R1C2 and R2C1 both contain 5\n"
+    );
+}
+
+#[test]
+fn validate_all_flags_the_invalid_lines() {
+    let input = "\
+53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+
+55..7....5..19.....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+53..7....6..x95....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+";
+    let output = run(&["--validate-all"], input);
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Line 3: Puzzle is invalid:\n  \
+         R1C1 and R1C2 both contain 5\n  \
+         R1C1 and R2C1 both contain 5\n  \
+         R1C2 and R2C1 both contain 5\n\
+         Line 4: Invalid character 'x' at line 4, column 13\n\
+         2 of 4 puzzles are invalid\n"
+    );
+}
+
+#[test]
+fn validate_all_succeeds_on_valid_puzzles() {
+    let input =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79\n";
+    let output = run(&["--validate-all"], input);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0 of 1 puzzles are invalid\n");
+
+    // Two 5s on the main diagonal are only a conflict in X-Sudoku
+    let input =
+        "5...............................................................................5\n";
+    assert!(run(&["--validate-all"], input).status.success());
+    let output = run(&["--validate-all", "--diagonal"], input);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("R1C1 and R9C9 both contain 5"));
+}
+
+#[test]
+fn batch_solves_every_line_in_order() {
+    let input = "\
+53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+
+123
+12345678.........9...............................................................
+11...............................................................................
+";
+    let output = run(&["--batch"], input);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179\n\
+         INVALID\n\
+         UNSOLVABLE\n\
+         INVALID\n"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Invalid puzzle on line 3"));
+    assert!(stderr.contains("Invalid puzzle on line 5"));
+}
+
+#[test]
+fn diff_shows_the_changed_cells() {
+    let a = temp_file(
+        "diff-a.txt",
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+    );
+    let b = temp_file(
+        "diff-b.txt",
+        "534.7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..7.",
+    );
+    let output = run(&["diff", a.to_str().unwrap(), b.to_str().unwrap()], "");
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines[0], "..4......");
+    assert_eq!(lines[8], "........_");
+    assert_eq!(lines[9], "2 differences");
+}
+
+#[test]
+fn repl_runs_scripted_commands() {
+    let script = "\
+candidates R1C3
+set R1C3 4
+set R1C3 1
+set R1C4 5
+bogus
+hint
+unset R1C3
+candidates R1C3
+solve
+quit
+";
+    let puzzle = temp_file(
+        "repl.txt",
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+    );
+    let output = run(&["repl", puzzle.to_str().unwrap()], script);
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    assert!(stdout.contains("> R1C3: 1 2 4\n"));
+    assert!(stdout.contains("> R1C3 is already filled\n"));
+    assert!(stdout.contains("> 5 cannot be placed at R1C4\n"));
+    assert!(stdout.contains("> Unknown command, type 'help' for a list of commands\n"));
+    assert!(stdout.contains("> R5C5 = 5\n"));
+    assert!(stdout.contains("> > R1C3: 1 2 4\n"));
+    assert!(stdout.contains("| 5 3 4 | 6 7 8 | 9 1 2 |"));
+    assert!(!stdout.contains("No solution found"));
+}
+
+#[test]
+fn repl_exits_at_the_end_of_input() {
+    let output = run(&["repl"], "set R1C1 5\nshow\n");
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("> "));
+}
+
+#[test]
+fn timeout_stops_the_solver() {
+    let hard = "000000010400000000020000000000050407008000300001090000300400200050100000000806000";
+    let output = run(&["--timeout", "0"], hard);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).ends_with("Timed out\n"));
+
+    let output = run(&["--timeout", "60000"], hard);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Solution:"));
+}
+
+#[test]
+fn puzzles_are_read_from_stdin() {
+    let puzzle = std::fs::read_to_string("input/test1.txt").unwrap();
+    let from_file = run(&["input/test1.txt"], "");
+    assert!(from_file.status.success());
+    let from_stdin = run(&[], &puzzle);
+    assert_eq!(stdout(&from_stdin), stdout(&from_file));
+    let from_dash = run(&["-"], &puzzle);
+    assert_eq!(stdout(&from_dash), stdout(&from_file));
+    assert!(stdout(&from_file).contains("Solution:"));
+}
+
+#[test]
+fn diagonal_flag_solves_x_sudoku() {
+    let output = run(&["--batch", "--diagonal"], &".".repeat(81));
+    assert!(output.status.success());
+    let solution: Vec<u32> = stdout(&output)
+        .trim()
+        .chars()
+        .map(|c| c.to_digit(10).unwrap())
+        .collect();
+    assert_eq!(solution.len(), 81);
+    let mut main: Vec<u32> = (0..9).map(|k| solution[k * 9 + k]).collect();
+    let mut anti: Vec<u32> = (0..9).map(|k| solution[k * 9 + 8 - k]).collect();
+    main.sort_unstable();
+    anti.sort_unstable();
+    assert_eq!(main, (1..=9).collect::<Vec<_>>());
+    assert_eq!(anti, main);
+}
+
+#[test]
+fn explain_json_lists_the_steps() {
+    let puzzle =
+        "......3.7..1..6.457.5.9..122..6....43...79.......48...5.2...86......5..1...9.....";
+    let output = run(&["--explain-json"], puzzle);
+    assert!(output.status.success());
+    let steps: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let steps = steps.as_array().unwrap();
+    let placed = steps.iter().filter(|step| step["type"] == "place").count();
+    assert_eq!(placed, puzzle.matches('.').count());
+    for step in steps {
+        let keys: Vec<&str> = step
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(keys.len(), 5);
+        for key in ["type", "cell", "value", "technique", "eliminations"] {
+            assert!(keys.contains(&key), "missing {}", key);
+        }
+        assert!(step["eliminations"].is_array());
+    }
+    let eliminate = steps
+        .iter()
+        .find(|step| step["type"] == "eliminate")
+        .unwrap();
+    assert_eq!(eliminate["technique"], "PointingPair");
+    assert!(eliminate["cell"].is_null());
+    assert!(eliminate["eliminations"][0]["cell"].is_string());
+    assert!(!steps.iter().any(|step| step["type"] == "guess"));
+}
+
+#[test]
+fn compare_lists_every_backend() {
+    let puzzle =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+    let file = temp_file("compare.txt", puzzle);
+    let output = run(&["--compare", file.to_str().unwrap()], "");
+    assert!(output.status.success());
+    let table = stdout(&output);
+    let backends: Vec<&str> = table
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+    assert_eq!(
+        backends,
+        [
+            "backtracking",
+            "mrv",
+            "mrv+propagation",
+            "mrv+random",
+            "mrv+degree",
+            "dlx"
+        ]
+    );
+    assert!(table.lines().skip(1).all(|line| line.contains(" solved ")));
+}
+
+#[test]
+fn generate_prints_distinct_valid_puzzles() {
+    let output = run(
+        &["generate", "--count", "5", "--clues", "32", "--seed", "7"],
+        "",
+    );
+    assert!(output.status.success());
+    let text = stdout(&output);
+    let puzzles: Vec<&str> = text.lines().collect();
+    assert_eq!(puzzles.len(), 5);
+    let distinct: std::collections::HashSet<&str> = puzzles.iter().copied().collect();
+    assert_eq!(distinct.len(), 5);
+    for puzzle in puzzles {
+        let grid = SudokuGrid::from_str_compact(puzzle).unwrap();
+        assert!(Solver::from_grid(grid).has_unique_solution());
+    }
+    let again = run(
+        &["generate", "--count", "5", "--clues", "32", "--seed", "7"],
+        "",
+    );
+    assert_eq!(stdout(&again), text);
+}
+
+#[test]
+fn generate_sorts_by_difficulty() {
+    let output = run(
+        &[
+            "generate", "--count", "8", "--clues", "24", "--seed", "3", "--sort",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let ratings: Vec<_> = stdout(&output)
+        .lines()
+        .map(|line| {
+            let grid = SudokuGrid::from_str_compact(line).unwrap();
+            Solver::from_grid(grid).rate().unwrap()
+        })
+        .collect();
+    assert_eq!(ratings.len(), 8);
+    assert!(ratings.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[test]
+fn generate_histogram_counts_every_puzzle() {
+    let output = run(
+        &[
+            "generate",
+            "--count",
+            "6",
+            "--clues",
+            "26",
+            "--seed",
+            "4",
+            "--histogram",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).lines().count(), 6);
+    let histogram = String::from_utf8(output.stderr).unwrap();
+    let tiers: Vec<(&str, usize)> = histogram
+        .lines()
+        .map(|line| {
+            let (tier, tally) = line.split_once(' ').unwrap();
+            (tier, tally.trim().parse().unwrap())
+        })
+        .collect();
+    let names: Vec<&str> = tiers.iter().map(|&(tier, _)| tier).collect();
+    assert_eq!(names, ["Easy", "Medium", "Hard", "Expert"]);
+    assert_eq!(tiers.iter().map(|&(_, tally)| tally).sum::<usize>(), 6);
+}
+
+#[test]
+fn index_solves_one_puzzle_of_a_file() {
+    let puzzles = temp_file(
+        "index.txt",
+        "\
+53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+.....1..74......39.21...4..94.......1..9...85.5..3....8...4....6....8..2....25.6.
+55..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79
+",
+    );
+    let path = puzzles.to_str().unwrap();
+    let output = run(&["--index", "1", path], "");
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.contains("| _ _ _ | _ _ 1 | _ _ 7 |"));
+    assert!(text.contains("| 5 3 9 | 4 6 1 | 8 2 7 |"));
+
+    let output = run(&["--index", "3", path], "");
+    assert!(!output.status.success());
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert!(error.contains("index 3 is out of range, the input has 3 puzzles"));
+}
+
+#[test]
+fn disjoint_groups_fill_every_position_of_the_squares() {
+    let empty = ".".repeat(81);
+    let output = run(&["--batch", "--disjoint-groups"], &empty);
+    assert!(output.status.success());
+    let solution: Vec<u32> = stdout(&output)
+        .trim()
+        .chars()
+        .map(|c| c.to_digit(10).unwrap())
+        .collect();
+    for position in 0..9 {
+        let mut group: Vec<u32> = (0..9)
+            .map(|square| {
+                let row = (square / 3) * 3 + position / 3;
+                let col = (square % 3) * 3 + position % 3;
+                solution[row * 9 + col]
+            })
+            .collect();
+        group.sort_unstable();
+        assert_eq!(group, (1..=9).collect::<Vec<u32>>());
+    }
+}
+
+#[test]
+fn variant_flags_combine() {
+    let empty = ".".repeat(81);
+    let output = run(&["--batch", "--nonconsecutive", "--diagonal"], &empty);
+    assert!(output.status.success());
+    let line = stdout(&output);
+    let grid = SudokuGrid::from_str_compact(line.trim()).unwrap();
+    let variants = Variant::Diagonal | Variant::NonConsecutive;
+    assert!(grid.with_variant(variants).is_ok());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn frames_writes_one_image_per_placement() {
+    let dir = std::env::temp_dir().join(format!("sudoku-rs-{}-frames", std::process::id()));
+    let puzzle =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+    let output = run(&["--frames", dir.to_str().unwrap()], puzzle);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("Solution:\n"));
+    // Every empty cell of the puzzle is placed at least once
+    let empty = puzzle.chars().filter(|&c| c == '.').count();
+    let mut frames: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    frames.sort();
+    assert!(frames.len() >= empty);
+    assert!(frames[0].ends_with("frame-0001.png"));
+    assert!(std::fs::read(&frames[0]).unwrap().starts_with(b"\x89PNG"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
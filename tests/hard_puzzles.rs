@@ -0,0 +1,59 @@
+//! Checks on puzzles that are hard for a backtracking search, ignored by default because
+//! they take a few seconds in debug builds. Run them with
+//! `cargo test --release --test hard_puzzles -- --ignored`.
+//!
+//! The puzzles in tests/data/hard-puzzles.txt are, in order: the puzzle published by
+//! Arto Inkala in 2012 as the world's hardest Sudoku, "AI Escargot" (Arto Inkala, 2006),
+//! "Easter Monster" (Sudoku Players' Forums, 2007), the "hardest" puzzle of Peter Norvig's
+//! essay on solving every Sudoku puzzle, and the first five puzzles of the top95 collection.
+
+use std::io::BufReader;
+
+use sudoku_rs::{
+    input::SudokuGrid,
+    solver::{CellOrder, Solver, SolverConfig},
+};
+
+fn hard_puzzles() -> Vec<SudokuGrid> {
+    let file = std::fs::File::open("tests/data/hard-puzzles.txt").unwrap();
+    SudokuGrid::stream_from_reader(BufReader::new(file))
+        .map(Result::unwrap)
+        .collect()
+}
+
+/// The number of guesses needed to solve each puzzle with a cell order
+fn guesses(puzzles: &[SudokuGrid], cell_order: CellOrder) -> Vec<u64> {
+    let config = SolverConfig {
+        cell_order,
+        ..SolverConfig::default()
+    };
+    puzzles
+        .iter()
+        .map(|puzzle| {
+            let (solution, stats) = Solver::from_grid(puzzle.clone())
+                .with_config(config)
+                .solve_with_stats();
+            assert!(solution.unwrap().is_solution_of(puzzle));
+            stats.guesses
+        })
+        .collect()
+}
+
+/// The degree tie-break does not win on every puzzle, but it needs fewer guesses
+/// than plain minimum remaining values over the whole collection.
+#[test]
+#[ignore]
+fn degree_tiebreak_guesses_less_in_total_on_hard_puzzles() {
+    let puzzles = hard_puzzles();
+    assert_eq!(puzzles.len(), 9);
+    let mrv = guesses(&puzzles, CellOrder::Mrv);
+    let degree = guesses(&puzzles, CellOrder::MostConstrainingVariable);
+    println!("mrv: {:?}\nmrv+degree: {:?}", mrv, degree);
+    let (mrv, degree) = (mrv.iter().sum::<u64>(), degree.iter().sum::<u64>());
+    assert!(
+        degree < mrv,
+        "{} guesses with the degree heuristic, {} without",
+        degree,
+        mrv
+    );
+}
@@ -0,0 +1,38 @@
+//! Solve the top1465 collection of hard puzzles, one puzzle of 81 cells per line.
+//!
+//! The collection is not bundled with the crate. Download it, then run
+//! `TOP1465=path/to/top1465.txt cargo test --release --test top1465 -- --ignored`.
+
+use std::{fs::File, io::BufReader};
+
+use sudoku_rs::{input::SudokuGrid, solver::Solver};
+
+#[test]
+#[ignore = "needs the top1465 collection, see the module docs"]
+fn solves_every_top1465_puzzle() {
+    let path = std::env::var("TOP1465").expect("TOP1465 should be the path of top1465.txt");
+    let reader = BufReader::new(File::open(&path).unwrap());
+    let mut count = 0;
+    for (line, puzzle) in SudokuGrid::stream_from_reader(reader).enumerate() {
+        let puzzle = puzzle.unwrap();
+        let solution = Solver::from_grid(puzzle.clone())
+            .solve()
+            .unwrap_or_else(|| panic!("no solution for the puzzle on line {}", line + 1));
+        assert!(solution.is_valid(), "line {}", line + 1);
+        assert!(
+            (0..81).all(|k| {
+                let given = puzzle.at(k / 9, k % 9);
+                solution.at(k / 9, k % 9) != 0 && (given == 0 || solution.at(k / 9, k % 9) == given)
+            }),
+            "line {}",
+            line + 1
+        );
+        assert!(
+            Solver::from_grid(puzzle).has_unique_solution(),
+            "line {}",
+            line + 1
+        );
+        count += 1;
+    }
+    assert_eq!(count, 1465);
+}